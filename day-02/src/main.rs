@@ -1,9 +1,52 @@
 use anyhow::{anyhow, Result};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Colour {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Colour {
+    const ALL: [Colour; 3] = [Colour::Red, Colour::Green, Colour::Blue];
+}
+
+impl std::fmt::Display for Colour {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Colour::Red => write!(f, "red"),
+            Colour::Green => write!(f, "green"),
+            Colour::Blue => write!(f, "blue"),
+        }
+    }
+}
+
+impl std::str::FromStr for Colour {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "red" => Ok(Colour::Red),
+            "green" => Ok(Colour::Green),
+            "blue" => Ok(Colour::Blue),
+            _ => Err(anyhow!("invalid colour: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Violation {
+    set: usize,
+    colour: Colour,
+    count: u32,
+    limit: u32,
+}
+
+#[derive(Debug, PartialEq)]
 struct Game {
     id: u32,
     sets: Vec<Set>,
@@ -13,19 +56,22 @@ impl std::str::FromStr for Game {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (prefix_str, sets_str) = s
-            .split_once(": ")
-            .ok_or(anyhow!("invalid game str: {}", s))?;
-        let (_, id_str) = prefix_str
-            .split_once(" ")
-            .ok_or(anyhow!("invalid game str: {}", s))?;
-        let id = id_str.parse::<u32>()?;
-        let sets = sets_str
-            .split("; ")
-            .map(|s| s.parse())
-            .collect::<Result<Vec<Set>>>()?;
+        let (game, diagnostics) = validate_line(1, s);
+        check_errors(&diagnostics)?;
+        game.ok_or(anyhow!("invalid game str: {}", s))
+    }
+}
 
-        Ok(Game { id, sets })
+impl std::fmt::Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Game {}: ", self.id)?;
+        for (i, set) in self.sets.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", set)?;
+        }
+        Ok(())
     }
 }
 
@@ -34,6 +80,27 @@ impl Game {
         self.sets.iter().all(|s| s.is_possible(bag))
     }
 
+    fn violations(&self, bag: &Set) -> Vec<Violation> {
+        self.sets
+            .iter()
+            .enumerate()
+            .flat_map(|(i, s)| {
+                Colour::ALL.into_iter().filter_map(move |c| {
+                    if s.get(c) > bag.get(c) {
+                        Some(Violation {
+                            set: i,
+                            colour: c,
+                            count: s.get(c),
+                            limit: bag.get(c),
+                        })
+                    } else {
+                        None
+                    }
+                })
+            })
+            .collect()
+    }
+
     fn min_bag(&self) -> Set {
         let red = self.sets.iter().map(|s| s.red).max().unwrap_or(0);
         let green = self.sets.iter().map(|s| s.green).max().unwrap_or(0);
@@ -43,7 +110,7 @@ impl Game {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Set {
     red: u32,
     green: u32,
@@ -54,25 +121,42 @@ impl std::str::FromStr for Set {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut set = Set {
-            red: 0,
-            green: 0,
-            blue: 0,
-        };
-        for chunk in s.split(", ") {
-            let (value_str, colour) = chunk
-                .split_once(" ")
-                .ok_or(anyhow!("invalid set str: {}", s))?;
-            let value = value_str.parse::<u32>()?;
-            match colour {
-                "red" => set.red = value,
-                "green" => set.green = value,
-                "blue" => set.blue = value,
-                _ => (),
+        let mut diagnostics = vec![];
+        let set = validate_set(
+            s.trim(),
+            s.len() - s.trim_start().len(),
+            &mut |offset, chunk, problem| {
+                diagnostics.push(Diagnostic {
+                    line: 1,
+                    col: offset + 1,
+                    chunk: chunk.to_string(),
+                    problem,
+                })
+            },
+        );
+        check_errors(&diagnostics)?;
+        Ok(set)
+    }
+}
+
+// Colours are written in a fixed order and zero counts are left out, except for
+// an empty set, which has nothing else to write and so is `0 red`.
+impl std::fmt::Display for Set {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut colours = Colour::ALL
+            .into_iter()
+            .filter(|c| self.get(*c) > 0)
+            .collect::<Vec<Colour>>();
+        if colours.is_empty() {
+            colours = vec![Colour::ALL[0]];
+        }
+        for (i, c) in colours.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
             }
+            write!(f, "{} {}", self.get(c), c)?;
         }
-
-        Ok(set)
+        Ok(())
     }
 }
 
@@ -84,20 +168,403 @@ impl Set {
     fn power(&self) -> u32 {
         self.red * self.green * self.blue
     }
+
+    fn get(&self, colour: Colour) -> u32 {
+        match colour {
+            Colour::Red => self.red,
+            Colour::Green => self.green,
+            Colour::Blue => self.blue,
+        }
+    }
+}
+
+fn impossible_games<'a>(games: &'a [Game], bag: &Set) -> Vec<(&'a Game, Vec<Violation>)> {
+    games
+        .iter()
+        .map(|g| (g, g.violations(bag)))
+        .filter(|(_, vs)| !vs.is_empty())
+        .collect()
+}
+
+fn min_bag(games: &[Game]) -> Set {
+    let bags = games.iter().map(|g| g.min_bag()).collect::<Vec<Set>>();
+    let red = bags.iter().map(|s| s.red).max().unwrap_or(0);
+    let green = bags.iter().map(|s| s.green).max().unwrap_or(0);
+    let blue = bags.iter().map(|s| s.blue).max().unwrap_or(0);
+
+    Set { red, green, blue }
+}
+
+fn most_powerful(games: &[Game]) -> Option<&Game> {
+    games.iter().max_by_key(|g| g.min_bag().power())
+}
+
+#[derive(Debug, PartialEq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq)]
+enum Problem {
+    MissingColon,
+    MissingPrefix,
+    InvalidId,
+    DuplicateId(u32, usize),
+    EmptySet,
+    InvalidChunk,
+    UnknownColour,
+    DuplicateColour(Colour),
+    ZeroCount(Colour),
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::MissingColon => write!(f, "missing ':' after game id"),
+            Problem::MissingPrefix => write!(f, "expected 'Game'"),
+            Problem::InvalidId => write!(f, "invalid game id"),
+            Problem::DuplicateId(id, line) => {
+                write!(f, "game {} already defined on line {}", id, line)
+            }
+            Problem::EmptySet => write!(f, "empty set"),
+            Problem::InvalidChunk => write!(f, "expected '<count> <colour>'"),
+            Problem::UnknownColour => write!(f, "unknown colour"),
+            Problem::DuplicateColour(c) => write!(f, "{} already drawn in this set", c),
+            Problem::ZeroCount(c) => write!(f, "zero {} drawn", c),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Diagnostic {
+    line: usize,
+    col: usize,
+    chunk: String,
+    problem: Problem,
+}
+
+impl Diagnostic {
+    fn severity(&self) -> Severity {
+        match self.problem {
+            Problem::ZeroCount(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {} in '{}'",
+            self.line, self.col, severity, self.problem, self.chunk
+        )
+    }
+}
+
+// Splits `s` on `sep` and trims every piece, keeping the byte offset at which
+// the trimmed piece starts so diagnostics can point back into the line.
+fn split_with_offsets(s: &str, sep: char) -> impl Iterator<Item = (usize, &str)> {
+    s.split(sep).scan(0, move |start, piece| {
+        let offset = *start + piece.len() - piece.trim_start().len();
+        *start += piece.len() + sep.len_utf8();
+        Some((offset, piece.trim()))
+    })
+}
+
+// Fails with every error in `diagnostics`, letting warnings through.
+fn check_errors(diagnostics: &[Diagnostic]) -> Result<()> {
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity() == Severity::Error)
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(errors.join("\n")))
+    }
+}
+
+// Parses one set that starts `offset` bytes into the line, reporting every
+// problem it finds. Chunks with errors are left out of the returned set.
+fn validate_set(s: &str, offset: usize, report: &mut impl FnMut(usize, &str, Problem)) -> Set {
+    let mut set = Set {
+        red: 0,
+        green: 0,
+        blue: 0,
+    };
+    if s.is_empty() {
+        report(offset, s, Problem::EmptySet);
+        return set;
+    }
+    let mut seen = vec![];
+    for (chunk_offset, chunk) in split_with_offsets(s, ',') {
+        let offset = offset + chunk_offset;
+        let Some((value_str, colour_str)) = chunk.split_once(char::is_whitespace) else {
+            report(offset, chunk, Problem::InvalidChunk);
+            continue;
+        };
+        let Ok(value) = value_str.parse::<u32>() else {
+            report(offset, chunk, Problem::InvalidChunk);
+            continue;
+        };
+        let Ok(colour) = colour_str.trim().parse::<Colour>() else {
+            report(offset, chunk, Problem::UnknownColour);
+            continue;
+        };
+        if seen.contains(&colour) {
+            report(offset, chunk, Problem::DuplicateColour(colour));
+            continue;
+        }
+        // `Display` writes an empty set as a lone `0 red`, so that isn't
+        // worth a warning.
+        let empty_set = colour == Colour::ALL[0] && !s.contains(',');
+        if value == 0 && !empty_set {
+            report(offset, chunk, Problem::ZeroCount(colour));
+        }
+        seen.push(colour);
+        match colour {
+            Colour::Red => set.red = value,
+            Colour::Green => set.green = value,
+            Colour::Blue => set.blue = value,
+        }
+    }
+    set
+}
+
+// The game comes back whenever the id could be read, even if some sets had
+// errors, so callers that only want the id still get it.
+fn validate_line(line: usize, s: &str) -> (Option<Game>, Vec<Diagnostic>) {
+    let mut diagnostics = vec![];
+    let mut report = |offset: usize, chunk: &str, problem: Problem| {
+        diagnostics.push(Diagnostic {
+            line,
+            col: offset + 1,
+            chunk: chunk.to_string(),
+            problem,
+        })
+    };
+
+    let Some((prefix_str, sets_str)) = s.split_once(':') else {
+        report(0, s, Problem::MissingColon);
+        return (None, diagnostics);
+    };
+
+    let sets_offset = prefix_str.len() + 1;
+    let prefix_offset = prefix_str.len() - prefix_str.trim_start().len();
+    let prefix_str = prefix_str.trim();
+    let mut id = None;
+    match prefix_str.split_once(char::is_whitespace) {
+        Some(("Game", id_str)) => match id_str.trim().parse::<u32>() {
+            Ok(i) => id = Some(i),
+            Err(_) => report(prefix_offset, prefix_str, Problem::InvalidId),
+        },
+        _ => report(prefix_offset, prefix_str, Problem::MissingPrefix),
+    }
+
+    let sets = split_with_offsets(sets_str, ';')
+        .map(|(set_offset, set_str)| validate_set(set_str, sets_offset + set_offset, &mut report))
+        .collect();
+
+    (id.map(|id| Game { id, sets }), diagnostics)
+}
+
+fn validate(lines: &[String]) -> Vec<Diagnostic> {
+    let mut ids: HashMap<u32, usize> = HashMap::new();
+    let mut diagnostics = vec![];
+    for (i, l) in lines.iter().enumerate() {
+        let (game, mut line_diagnostics) = validate_line(i + 1, l);
+        diagnostics.append(&mut line_diagnostics);
+        if let Some(Game { id, .. }) = game {
+            if let Some(first) = ids.get(&id) {
+                let (prefix_str, _) = l.split_once(':').unwrap();
+                diagnostics.push(Diagnostic {
+                    line: i + 1,
+                    col: prefix_str.len() - prefix_str.trim_start().len() + 1,
+                    chunk: prefix_str.trim().to_string(),
+                    problem: Problem::DuplicateId(id, *first),
+                });
+            } else {
+                ids.insert(id, i + 1);
+            }
+        }
+    }
+
+    diagnostics
+}
+
+// Treats every set as a draw without replacement from the bag (the cubes go
+// back in before the next set), so the chance of a set is multivariate
+// hypergeometric. Candidate bags are every composition that covers the game's
+// minimal bag and holds at most `max_total` cubes, weighted uniformly.
+struct Estimator {
+    max_total: u32,
+    ln_fact: Vec<f64>,
+}
+
+impl Estimator {
+    // There are O(max_total³) candidate bags per game, so the bound has to
+    // stay small for the search to finish.
+    const MAX_TOTAL: u32 = 100;
+
+    fn new(max_total: u32) -> Estimator {
+        let mut ln_fact = vec![0.0];
+        for n in 1..=max_total {
+            ln_fact.push(ln_fact[n as usize - 1] + (n as f64).ln());
+        }
+        Estimator { max_total, ln_fact }
+    }
+
+    fn ln_choose(&self, n: u32, k: u32) -> f64 {
+        self.ln_fact[n as usize] - self.ln_fact[k as usize] - self.ln_fact[(n - k) as usize]
+    }
+
+    fn log_likelihood(&self, game: &Game, bag: &Set) -> Option<f64> {
+        let total = bag.red + bag.green + bag.blue;
+        if !game.is_possible(bag) || total > self.max_total {
+            return None;
+        }
+        Some(
+            game.sets
+                .iter()
+                .map(|s| {
+                    Colour::ALL
+                        .into_iter()
+                        .map(|c| self.ln_choose(bag.get(c), s.get(c)))
+                        .sum::<f64>()
+                        - self.ln_choose(total, s.red + s.green + s.blue)
+                })
+                .sum(),
+        )
+    }
+
+    fn candidates(&self, game: &Game) -> Vec<(Set, f64)> {
+        let min = game.min_bag();
+        let mut candidates = vec![];
+        for red in min.red..=self.max_total {
+            for green in min.green..=self.max_total.saturating_sub(red) {
+                for blue in min.blue..=self.max_total.saturating_sub(red + green) {
+                    let bag = Set { red, green, blue };
+                    if let Some(ll) = self.log_likelihood(game, &bag) {
+                        candidates.push((bag, ll));
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    // The most likely bag for `game` and the probability that the game is
+    // possible with `bag`, or None if no bag within `max_total` fits the game.
+    fn estimate(&self, game: &Game, bag: &Set) -> Option<(Set, f64)> {
+        let mut candidates = self.candidates(game);
+        let mut best = 0;
+        for (i, (_, ll)) in candidates.iter().enumerate() {
+            if *ll > candidates[best].1 {
+                best = i;
+            }
+        }
+        let max = candidates.get(best)?.1;
+        let (within, all) = candidates
+            .iter()
+            .fold((0.0, 0.0), |(within, all), (candidate, ll)| {
+                let weight = (ll - max).exp();
+                if candidate.is_possible(bag) {
+                    (within + weight, all + weight)
+                } else {
+                    (within, all + weight)
+                }
+            });
+        Some((candidates.swap_remove(best).0, within / all))
+    }
+}
+
+struct Config {
+    input: String,
+    bag: Set,
+    normalise: bool,
+    estimate: Option<u32>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config> {
+    let mut config = Config {
+        input: "input.txt".to_string(),
+        bag: Set {
+            red: 12,
+            green: 13,
+            blue: 14,
+        },
+        normalise: false,
+        estimate: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bag" => {
+                let bag_str = args.next().ok_or(anyhow!("missing value for --bag"))?;
+                config.bag = bag_str.parse()?;
+            }
+            "--bag-file" => {
+                let path = args.next().ok_or(anyhow!("missing value for --bag-file"))?;
+                config.bag = std::fs::read_to_string(path)?.trim().parse()?;
+            }
+            "--normalise" => config.normalise = true,
+            "--estimate" => {
+                let max_str = args.next().ok_or(anyhow!("missing value for --estimate"))?;
+                let max_total = max_str.parse()?;
+                if max_total > Estimator::MAX_TOTAL {
+                    return Err(anyhow!(
+                        "--estimate must be at most {}",
+                        Estimator::MAX_TOTAL
+                    ));
+                }
+                config.estimate = Some(max_total);
+            }
+            _ if arg.starts_with("--") => return Err(anyhow!("unknown option: {}", arg)),
+            _ => config.input = arg,
+        }
+    }
+
+    Ok(config)
 }
 
 fn main() -> Result<()> {
-    let input = read_input("input.txt")?;
+    let Config {
+        input,
+        bag,
+        normalise,
+        estimate,
+    } = parse_args(std::env::args().skip(1))?;
+    let input = read_input(input)?;
+
+    let diagnostics = validate(&input);
+    for d in &diagnostics {
+        eprintln!("{}", d);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity() == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(anyhow!("{} errors in input", errors));
+    }
+
     let games = input
         .iter()
         .map(|l| l.parse::<Game>())
         .collect::<Result<Vec<Game>>>()?;
 
-    let bag = Set {
-        red: 12,
-        green: 13,
-        blue: 14,
-    };
+    if normalise {
+        for game in &games {
+            println!("{}", game);
+        }
+        return Ok(());
+    }
 
     let sol1: u32 = games
         .iter()
@@ -109,6 +576,34 @@ fn main() -> Result<()> {
     let sol2: u32 = games.iter().map(|g| g.min_bag().power()).sum();
     dbg!(sol2);
 
+    for (game, violations) in impossible_games(&games, &bag) {
+        for v in violations {
+            println!(
+                "game {} impossible: set {} has {} {} (bag has {})",
+                game.id,
+                v.set + 1,
+                v.count,
+                v.colour,
+                v.limit
+            );
+        }
+    }
+    dbg!(min_bag(&games));
+    dbg!(most_powerful(&games).map(|g| g.id));
+
+    if let Some(max_total) = estimate {
+        let estimator = Estimator::new(max_total);
+        for game in &games {
+            match estimator.estimate(game, &bag) {
+                Some((mle, p)) => println!(
+                    "game {}: most likely bag {}, P(possible) = {:.4}",
+                    game.id, mle, p
+                ),
+                None => println!("game {}: needs more than {} cubes", game.id, max_total),
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -116,3 +611,254 @@ fn read_input<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<String>> {
     let file = File::open(path)?;
     BufReader::new(file).lines().map(|l| Ok(l?)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        impossible_games, min_bag, most_powerful, parse_args, validate, Colour, Diagnostic,
+        Estimator, Game, Problem, Set, Severity,
+    };
+    use anyhow::Result;
+
+    fn example() -> Result<Vec<Game>> {
+        [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+            "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red",
+            "Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green",
+        ]
+        .iter()
+        .map(|l| l.parse())
+        .collect()
+    }
+
+    #[test]
+    fn test_impossible_games() -> Result<()> {
+        let games = example()?;
+        let bag = Set {
+            red: 12,
+            green: 13,
+            blue: 14,
+        };
+        let impossible = impossible_games(&games, &bag);
+        assert_eq!(
+            impossible.iter().map(|(g, _)| g.id).collect::<Vec<u32>>(),
+            vec![3, 4]
+        );
+        let (_, violations) = &impossible[0];
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].set, 0);
+        assert_eq!(violations[0].colour, Colour::Red);
+        assert_eq!(violations[0].count, 20);
+        assert_eq!(violations[0].limit, 12);
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_bag_and_most_powerful() -> Result<()> {
+        let games = example()?;
+        assert_eq!(
+            min_bag(&games),
+            Set {
+                red: 20,
+                green: 13,
+                blue: 15
+            }
+        );
+        assert_eq!(most_powerful(&games).map(|g| g.id), Some(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_round_trip() -> Result<()> {
+        for game in example()? {
+            let rendered = game.to_string();
+            assert_eq!(rendered.parse::<Game>()?, game);
+            assert_eq!(rendered.parse::<Game>()?.to_string(), rendered);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_normalises() -> Result<()> {
+        let game = "Game  7 :3 blue,4 red ;  2 green, 1 red;0 blue".parse::<Game>()?;
+        assert_eq!(
+            game.to_string(),
+            "Game 7: 4 red, 3 blue; 1 red, 2 green; 0 red"
+        );
+        assert_eq!(game.to_string().parse::<Game>()?, game);
+
+        let normalised = [
+            "Game 1: 0 red, 0 green ; 2 blue",
+            "Game 2: 0 green",
+            "Game 3: 5 red,0 blue",
+        ]
+        .iter()
+        .map(|l| Ok(l.parse::<Game>()?.to_string()))
+        .collect::<Result<Vec<String>>>()?;
+        assert_eq!(
+            normalised,
+            ["Game 1: 0 red; 2 blue", "Game 2: 0 red", "Game 3: 5 red"]
+        );
+        assert_eq!(validate(&normalised), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let lines = [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 3 red, 5 red; 0 blue",
+            "Gmae 3 : 1 red, 1 pink",
+            "Game 1: 1 red;",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<String>>();
+
+        let diagnostics = validate(&lines);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    line: 2,
+                    col: 16,
+                    chunk: "5 red".to_string(),
+                    problem: Problem::DuplicateColour(Colour::Red),
+                },
+                Diagnostic {
+                    line: 2,
+                    col: 23,
+                    chunk: "0 blue".to_string(),
+                    problem: Problem::ZeroCount(Colour::Blue),
+                },
+                Diagnostic {
+                    line: 3,
+                    col: 1,
+                    chunk: "Gmae 3".to_string(),
+                    problem: Problem::MissingPrefix,
+                },
+                Diagnostic {
+                    line: 3,
+                    col: 17,
+                    chunk: "1 pink".to_string(),
+                    problem: Problem::UnknownColour,
+                },
+                Diagnostic {
+                    line: 4,
+                    col: 15,
+                    chunk: "".to_string(),
+                    problem: Problem::EmptySet,
+                },
+                Diagnostic {
+                    line: 4,
+                    col: 1,
+                    chunk: "Game 1".to_string(),
+                    problem: Problem::DuplicateId(1, 1),
+                },
+            ]
+        );
+        assert_eq!(diagnostics[1].severity(), Severity::Warning);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "2:16: error: red already drawn in this set in '5 red'"
+        );
+
+        // Parsing goes through the same checks, so it fails on any error and
+        // lets warnings through.
+        assert!(lines[0].parse::<Game>().is_ok());
+        assert!(lines[1..].iter().all(|l| l.parse::<Game>().is_err()));
+        assert!("Gmae 3: 1 red".parse::<Game>().is_err());
+        assert!("Game 3: 0 red".parse::<Game>().is_ok());
+        assert_eq!(
+            "1 red, 2 pink"
+                .parse::<Set>()
+                .map_err(|e| e.to_string())
+                .unwrap_err(),
+            "1:8: error: unknown colour in '2 pink'"
+        );
+    }
+
+    #[test]
+    fn test_estimator() -> Result<()> {
+        let estimator = Estimator::new(10);
+        let game = "Game 1: 1 red".parse::<Game>()?;
+        let ll = estimator.log_likelihood(
+            &game,
+            &Set {
+                red: 1,
+                green: 1,
+                blue: 0,
+            },
+        );
+        assert!((ll.unwrap().exp() - 0.5).abs() < 1e-9);
+        assert_eq!(
+            estimator.log_likelihood(
+                &game,
+                &Set {
+                    red: 0,
+                    green: 1,
+                    blue: 0,
+                }
+            ),
+            None
+        );
+
+        let game = "Game 2: 1 red, 1 green; 1 red, 1 green".parse::<Game>()?;
+        let bag = Set {
+            red: 10,
+            green: 10,
+            blue: 4,
+        };
+        let (mle, _) = estimator.estimate(&game, &bag).unwrap();
+        assert_eq!(
+            mle,
+            Set {
+                red: 1,
+                green: 1,
+                blue: 0,
+            }
+        );
+
+        let game = "Game 3: 3 blue".parse::<Game>()?;
+        let (_, p) = estimator.estimate(&game, &bag).unwrap();
+        assert!(p > 0.0 && p < 1.0);
+        let bag = Set {
+            red: 10,
+            green: 10,
+            blue: 2,
+        };
+        let (_, p) = estimator.estimate(&game, &bag).unwrap();
+        assert_eq!(p, 0.0);
+        assert_eq!(Estimator::new(2).estimate(&game, &bag), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_args() -> Result<()> {
+        let args = ["games.txt", "--bag", "1 red, 2 green, 3 blue"];
+        let config = parse_args(args.iter().map(|s| s.to_string()))?;
+        assert_eq!(config.input, "games.txt");
+        assert_eq!(
+            config.bag,
+            Set {
+                red: 1,
+                green: 2,
+                blue: 3
+            }
+        );
+        assert!(parse_args(["--bag"].iter().map(|s| s.to_string())).is_err());
+        assert!(parse_args(["--bag", "12 reds"].iter().map(|s| s.to_string())).is_err());
+        assert!(parse_args(["--bag", "3 red, 5 red"].iter().map(|s| s.to_string())).is_err());
+        assert!("1 red, 2 purple".parse::<Set>().is_err());
+        assert_eq!(
+            parse_args(["--estimate", "100"].iter().map(|s| s.to_string()))?.estimate,
+            Some(100)
+        );
+        assert!(parse_args(["--estimate", "101"].iter().map(|s| s.to_string())).is_err());
+        assert!(parse_args(["--estimate", "4000000000"].iter().map(|s| s.to_string())).is_err());
+        assert!("Game 1: 3 blue, 1 blue".parse::<Game>().is_err());
+        Ok(())
+    }
+}
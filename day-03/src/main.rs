@@ -1,76 +1,586 @@
-use anyhow::Result;
-use regex::Regex;
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 
+struct Config {
+    input: String,
+    ansi: bool,
+    html: Option<String>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config> {
+    let mut config = Config {
+        input: "input.txt".to_string(),
+        ansi: false,
+        html: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ansi" => config.ansi = true,
+            "--html" => {
+                let path = args.next().ok_or(anyhow!("missing value for --html"))?;
+                config.html = Some(path);
+            }
+            _ if arg.starts_with("--") => return Err(anyhow!("unknown option: {}", arg)),
+            _ => config.input = arg,
+        }
+    }
+
+    Ok(config)
+}
+
 fn main() -> Result<()> {
-    let input = read_input("input.txt")?;
+    let Config { input, ansi, html } = parse_args(std::env::args().skip(1))?;
+    let input = read_input(input)?;
+    let schematic = Schematic::parse(&input)?;
 
-    let symbols = input
-        .iter()
-        .enumerate()
-        .flat_map(|(i, l)| extract_labels(l, r"[^0-9\.]+", i))
-        .collect::<Vec<Label>>();
-    let numbers = input
-        .iter()
-        .enumerate()
-        .flat_map(|(i, l)| extract_labels(l, r"\d+", i))
-        .collect::<Vec<Label>>();
+    if ansi {
+        print!("{}", render_ansi(&input, &schematic));
+    }
+    if let Some(path) = html {
+        std::fs::write(path, render_html(&input, &schematic))?;
+    }
 
-    let sol1 = numbers
+    let sol1 = schematic
+        .part_numbers()
         .iter()
-        .filter(|n| symbols.iter().any(|s| s.is_adiacent(n)))
-        .map(|n| n.value.parse::<u32>().unwrap())
+        .map(|n| n.value)
         .sum::<u32>();
     dbg!(sol1);
 
-    let sol2 = symbols
-        .iter()
-        .filter_map(|s| {
-            let ns: Vec<&Label> = numbers.iter().filter(|n| n.is_adiacent(s)).collect();
-            if ns.len() == 2 {
-                Some(ns[0].value.parse::<u32>().unwrap() * ns[1].value.parse::<u32>().unwrap())
-            } else {
-                None
-            }
-        })
-        .sum::<u32>();
+    let sol2 = schematic.gears().iter().map(|g| g.ratio()).sum::<u32>();
     dbg!(sol2);
 
+    let orphans = schematic.orphan_numbers().len();
+    dbg!(orphans);
+    for (kind, symbols) in schematic.symbols_by_kind() {
+        println!("{}: {} symbols", kind, symbols.len());
+    }
+    if let Some(gear) = schematic.gears().iter().max_by_key(|g| g.ratio()) {
+        println!(
+            "largest gear at {}:{} with ratio {}",
+            gear.symbol.span.row + 1,
+            gear.symbol.span.start + 1,
+            gear.ratio()
+        );
+    }
+
     Ok(())
 }
 
-#[derive(Debug)]
-struct Label {
-    value: String,
+// A horizontal run of cells on one row, `start` inclusive and `end` exclusive.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Span {
     row: usize,
-    col: usize,
+    start: usize,
+    end: usize,
 }
 
-impl Label {
-    fn is_adiacent(&self, other: &Label) -> bool {
-        let prev_row = if self.row > 0 { self.row - 1 } else { 0 };
-        let prev_col = if self.col > 0 { self.col - 1 } else { 0 };
-        other.row >= prev_row
-            && other.row <= self.row + 1
-            && (other.col + other.value.len() - 1) >= prev_col
-            && other.col <= self.col + self.value.len()
+impl Span {
+    fn cell(row: usize, col: usize) -> Span {
+        Span {
+            row,
+            start: col,
+            end: col + 1,
+        }
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.start..self.end).map(|col| (self.row, col))
+    }
+
+    // Every cell touching the span, including diagonals and the span itself.
+    fn surrounding(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let rows = self.row.saturating_sub(1)..=self.row + 1;
+        rows.flat_map(|row| (self.start.saturating_sub(1)..=self.end).map(move |col| (row, col)))
+    }
+
+    fn is_adjacent(&self, other: &Span) -> bool {
+        self.row.abs_diff(other.row) <= 1 && self.start <= other.end && other.start <= self.end
     }
 }
 
-fn extract_labels(s: &str, re: &str, row: usize) -> Vec<Label> {
-    let re = Regex::new(re).unwrap();
-    re.find_iter(s)
-        .map(|m| Label {
-            value: m.as_str().to_string(),
-            row,
-            col: m.start(),
+#[derive(Debug, PartialEq)]
+struct Number {
+    value: u32,
+    span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+struct Symbol {
+    kind: char,
+    span: Span,
+}
+
+#[derive(Debug)]
+struct Gear<'a> {
+    symbol: &'a Symbol,
+    numbers: Vec<&'a Number>,
+}
+
+impl Gear<'_> {
+    fn ratio(&self) -> u32 {
+        self.numbers.iter().map(|n| n.value).product()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cell {
+    Number(usize),
+    Symbol(usize),
+}
+
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    cells: HashMap<(usize, usize), Cell>,
+}
+
+impl Schematic {
+    fn parse(lines: &[String]) -> Result<Schematic> {
+        let mut numbers = vec![];
+        let mut symbols = vec![];
+        for (row, line) in lines.iter().enumerate() {
+            let mut current: Option<Number> = None;
+            for (col, c) in line.chars().chain(std::iter::once('.')).enumerate() {
+                if let Some(digit) = c.to_digit(10) {
+                    let number = current.get_or_insert(Number {
+                        value: 0,
+                        span: Span::cell(row, col),
+                    });
+                    number.value = number
+                        .value
+                        .checked_mul(10)
+                        .and_then(|v| v.checked_add(digit))
+                        .ok_or(anyhow!(
+                            "number too large at {}:{}",
+                            row + 1,
+                            number.span.start + 1
+                        ))?;
+                    number.span.end = col + 1;
+                    continue;
+                }
+                if let Some(number) = current.take() {
+                    numbers.push(number);
+                }
+                if c != '.' {
+                    symbols.push(Symbol {
+                        kind: c,
+                        span: Span::cell(row, col),
+                    });
+                }
+            }
+        }
+
+        let mut cells = HashMap::new();
+        for (i, n) in numbers.iter().enumerate() {
+            for cell in n.span.cells() {
+                cells.insert(cell, Cell::Number(i));
+            }
+        }
+        for (i, s) in symbols.iter().enumerate() {
+            for cell in s.span.cells() {
+                cells.insert(cell, Cell::Symbol(i));
+            }
+        }
+
+        Ok(Schematic {
+            numbers,
+            symbols,
+            cells,
         })
-        .collect()
+    }
+
+    fn neighbours(&self, span: &Span) -> Vec<Cell> {
+        span.surrounding()
+            .filter_map(|cell| self.cells.get(&cell).copied())
+            .filter(|cell| span.is_adjacent(self.span_of(*cell)))
+            .collect()
+    }
+
+    fn span_of(&self, cell: Cell) -> &Span {
+        match cell {
+            Cell::Number(i) => &self.numbers[i].span,
+            Cell::Symbol(i) => &self.symbols[i].span,
+        }
+    }
+
+    fn adjacent_numbers(&self, symbol: &Symbol) -> Vec<&Number> {
+        let mut found = self
+            .neighbours(&symbol.span)
+            .into_iter()
+            .filter_map(|c| match c {
+                Cell::Number(i) => Some(i),
+                Cell::Symbol(_) => None,
+            })
+            .collect::<Vec<usize>>();
+        found.sort();
+        found.dedup();
+        found.into_iter().map(|i| &self.numbers[i]).collect()
+    }
+
+    fn adjacent_symbols(&self, number: &Number) -> Vec<&Symbol> {
+        self.neighbours(&number.span)
+            .into_iter()
+            .filter_map(|c| match c {
+                Cell::Symbol(i) => Some(&self.symbols[i]),
+                Cell::Number(_) => None,
+            })
+            .collect()
+    }
+
+    fn part_numbers(&self) -> Vec<&Number> {
+        self.numbers
+            .iter()
+            .filter(|n| !self.adjacent_symbols(n).is_empty())
+            .collect()
+    }
+
+    fn orphan_numbers(&self) -> Vec<&Number> {
+        self.numbers
+            .iter()
+            .filter(|n| self.adjacent_symbols(n).is_empty())
+            .collect()
+    }
+
+    fn symbols_by_kind(&self) -> BTreeMap<char, Vec<&Symbol>> {
+        let mut kinds: BTreeMap<char, Vec<&Symbol>> = BTreeMap::new();
+        for s in &self.symbols {
+            kinds.entry(s.kind).or_default().push(s);
+        }
+        kinds
+    }
+
+    fn gears(&self) -> Vec<Gear<'_>> {
+        self.gears_with('*', 2)
+    }
+
+    fn gears_with(&self, kind: char, neighbours: usize) -> Vec<Gear<'_>> {
+        self.symbols
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| Gear {
+                symbol: s,
+                numbers: self.adjacent_numbers(s),
+            })
+            .filter(|g| g.numbers.len() == neighbours)
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Highlight {
+    Part,
+    Orphan,
+    Symbol,
+    Gear,
+}
+
+impl Highlight {
+    fn ansi(&self) -> &'static str {
+        match self {
+            Highlight::Part => "\x1b[1;32m",
+            Highlight::Orphan => "\x1b[2m",
+            Highlight::Symbol => "\x1b[33m",
+            Highlight::Gear => "\x1b[1;31m",
+        }
+    }
+
+    fn class(&self) -> &'static str {
+        match self {
+            Highlight::Part => "part",
+            Highlight::Orphan => "orphan",
+            Highlight::Symbol => "symbol",
+            Highlight::Gear => "gear",
+        }
+    }
+}
+
+struct Annotations {
+    highlights: HashMap<(usize, usize), Highlight>,
+    ratios: BTreeMap<usize, Vec<u32>>,
+}
+
+impl Schematic {
+    fn annotate(&self) -> Annotations {
+        let mut highlights = HashMap::new();
+        for n in &self.numbers {
+            let highlight = if self.adjacent_symbols(n).is_empty() {
+                Highlight::Orphan
+            } else {
+                Highlight::Part
+            };
+            for cell in n.span.cells() {
+                highlights.insert(cell, highlight);
+            }
+        }
+        for s in &self.symbols {
+            for cell in s.span.cells() {
+                highlights.insert(cell, Highlight::Symbol);
+            }
+        }
+        let mut ratios: BTreeMap<usize, Vec<u32>> = BTreeMap::new();
+        for g in self.gears() {
+            for cell in g.symbol.span.cells() {
+                highlights.insert(cell, Highlight::Gear);
+            }
+            ratios.entry(g.symbol.span.row).or_default().push(g.ratio());
+        }
+        Annotations { highlights, ratios }
+    }
+}
+
+// Splits a line into runs of characters sharing the same highlight.
+fn segments(
+    line: &str,
+    row: usize,
+    highlights: &HashMap<(usize, usize), Highlight>,
+) -> Vec<(Option<Highlight>, String)> {
+    let mut segments: Vec<(Option<Highlight>, String)> = vec![];
+    for (col, c) in line.chars().enumerate() {
+        let highlight = highlights.get(&(row, col)).copied();
+        match segments.last_mut() {
+            Some((h, text)) if *h == highlight => text.push(c),
+            _ => segments.push((highlight, c.to_string())),
+        }
+    }
+    segments
+}
+
+fn render_ansi(lines: &[String], schematic: &Schematic) -> String {
+    let annotations = schematic.annotate();
+    let mut out = String::new();
+    for (row, line) in lines.iter().enumerate() {
+        for (highlight, text) in segments(line, row, &annotations.highlights) {
+            match highlight {
+                Some(h) => out.push_str(&format!("{}{}\x1b[0m", h.ansi(), text)),
+                None => out.push_str(&text),
+            }
+        }
+        if let Some(ratios) = annotations.ratios.get(&row) {
+            let ratios = ratios.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+            out.push_str(&format!(
+                "  {}gears: {}\x1b[0m",
+                Highlight::Gear.ansi(),
+                ratios.join(", ")
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn render_html(lines: &[String], schematic: &Schematic) -> String {
+    let annotations = schematic.annotate();
+    let mut out = String::from(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<style>
+body { background: #111; color: #777; }
+.part { color: #4c4; font-weight: bold; }
+.orphan { color: #444; }
+.symbol { color: #cc4; }
+.gear { color: #e44; font-weight: bold; }
+.ratios { color: #e44; }
+</style>
+</head>
+<body>
+<pre>
+",
+    );
+    for (row, line) in lines.iter().enumerate() {
+        for (highlight, text) in segments(line, row, &annotations.highlights) {
+            match highlight {
+                Some(h) => out.push_str(&format!(
+                    "<span class=\"{}\">{}</span>",
+                    h.class(),
+                    escape_html(&text)
+                )),
+                None => out.push_str(&escape_html(&text)),
+            }
+        }
+        if let Some(ratios) = annotations.ratios.get(&row) {
+            let ratios = ratios.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+            out.push_str(&format!(
+                "  <span class=\"ratios\">gears: {}</span>",
+                ratios.join(", ")
+            ));
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
 }
 
 fn read_input<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<String>> {
     let file = File::open(path)?;
     BufReader::new(file).lines().map(|l| Ok(l?)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, read_input, render_ansi, render_html, Highlight, Schematic, Span};
+    use anyhow::Result;
+
+    #[test]
+    fn test_example() -> Result<()> {
+        let schematic = Schematic::parse(&read_input("input-small.txt")?)?;
+        assert_eq!(
+            schematic
+                .part_numbers()
+                .iter()
+                .map(|n| n.value)
+                .sum::<u32>(),
+            4361
+        );
+        assert_eq!(
+            schematic.gears().iter().map(|g| g.ratio()).sum::<u32>(),
+            467835
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_queries() -> Result<()> {
+        let schematic = Schematic::parse(&read_input("input-small.txt")?)?;
+        assert_eq!(
+            schematic
+                .orphan_numbers()
+                .iter()
+                .map(|n| n.value)
+                .collect::<Vec<u32>>(),
+            vec![114, 58]
+        );
+        let kinds = schematic.symbols_by_kind();
+        assert_eq!(kinds.keys().collect::<String>(), "#$*+");
+        assert_eq!(kinds[&'*'].len(), 3);
+
+        let dollar = kinds[&'$'][0];
+        assert_eq!(
+            schematic
+                .adjacent_numbers(dollar)
+                .iter()
+                .map(|n| n.value)
+                .collect::<Vec<u32>>(),
+            vec![664]
+        );
+
+        let gears = schematic.gears();
+        assert_eq!(gears.len(), 2);
+        assert_eq!(gears[0].symbol.span, Span::cell(1, 3));
+        assert_eq!(schematic.gears_with('*', 1).len(), 1);
+        Ok(())
+    }
+
+    fn schematic(lines: &[&str]) -> Result<Schematic> {
+        Schematic::parse(&lines.iter().map(|l| l.to_string()).collect::<Vec<String>>())
+    }
+
+    fn values(schematic: &Schematic) -> Vec<u32> {
+        schematic.part_numbers().iter().map(|n| n.value).collect()
+    }
+
+    #[test]
+    fn test_span_is_adjacent() {
+        let number = Span {
+            row: 1,
+            start: 2,
+            end: 5,
+        };
+        assert!(number.is_adjacent(&Span::cell(0, 1)));
+        assert!(number.is_adjacent(&Span::cell(0, 5)));
+        assert!(number.is_adjacent(&Span::cell(2, 1)));
+        assert!(number.is_adjacent(&Span::cell(2, 5)));
+        assert!(number.is_adjacent(&Span::cell(1, 1)));
+        assert!(number.is_adjacent(&Span::cell(1, 5)));
+        assert!(!number.is_adjacent(&Span::cell(1, 0)));
+        assert!(!number.is_adjacent(&Span::cell(1, 6)));
+        assert!(!number.is_adjacent(&Span::cell(3, 3)));
+        assert!(Span::cell(0, 0).is_adjacent(&Span::cell(1, 1)));
+    }
+
+    #[test]
+    fn test_edges_and_corners() -> Result<()> {
+        let s = schematic(&["12.....34", "..*.....#", "5........", "$.......6"])?;
+        assert_eq!(values(&s), vec![12, 34, 5]);
+
+        let s = schematic(&["*.....", ".12..3", "4....."])?;
+        assert_eq!(values(&s), vec![12]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_touching_symbols() -> Result<()> {
+        let s = schematic(&["10.20", "..*#.", "....3"])?;
+        assert_eq!(s.symbols.len(), 2);
+        assert_eq!(values(&s), vec![10, 20, 3]);
+        let gears = s.gears();
+        assert_eq!(gears.len(), 1);
+        assert_eq!(gears[0].ratio(), 200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_lines() -> Result<()> {
+        let s = schematic(&["7..", "", "*..", "", "..8"])?;
+        assert_eq!(values(&s), vec![]);
+        assert_eq!(s.symbols[0].span, Span::cell(2, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_annotate() -> Result<()> {
+        let s = schematic(&["467..114..", "...*......", "..35..633."])?;
+        let annotations = s.annotate();
+        assert_eq!(annotations.highlights[&(0, 0)], Highlight::Part);
+        assert_eq!(annotations.highlights[&(0, 5)], Highlight::Orphan);
+        assert_eq!(annotations.highlights[&(1, 3)], Highlight::Gear);
+        assert_eq!(annotations.highlights[&(2, 6)], Highlight::Orphan);
+        assert_eq!(annotations.highlights.get(&(0, 3)), None);
+        assert_eq!(annotations.ratios[&1], vec![16345]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let lines = vec!["1.2".to_string(), "<..".to_string()];
+        let s = Schematic::parse(&lines)?;
+        assert_eq!(
+            render_ansi(&lines, &s),
+            "\x1b[1;32m1\x1b[0m.\x1b[2m2\x1b[0m\n\x1b[33m<\x1b[0m..\n"
+        );
+        let html = render_html(&lines, &s);
+        assert!(html.contains(
+            "<span class=\"part\">1</span>.<span class=\"orphan\">2</span>\n<span class=\"symbol\">&lt;</span>..\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_args() -> Result<()> {
+        let args = |s: &[&str]| s.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+        let config = parse_args(args(&["--ansi", "grid.txt", "--html", "out.html"]).into_iter())?;
+        assert_eq!(config.input, "grid.txt");
+        assert!(config.ansi);
+        assert_eq!(config.html.as_deref(), Some("out.html"));
+
+        let config = parse_args(args(&[]).into_iter())?;
+        assert_eq!(config.input, "input.txt");
+        assert!(!config.ansi);
+        assert_eq!(config.html, None);
+
+        assert!(parse_args(args(&["--html"]).into_iter()).is_err());
+        assert!(parse_args(args(&["--asni"]).into_iter()).is_err());
+        Ok(())
+    }
+}
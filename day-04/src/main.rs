@@ -1,69 +1,268 @@
 use anyhow::{anyhow, Result};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::num::NonZeroU32;
 
 fn main() -> Result<()> {
+    let Config {
+        explain,
+        points,
+        copies,
+        bench,
+    } = parse_args(std::env::args().skip(1))?;
+    if let Some(size) = bench {
+        return run_bench(size);
+    }
+
     let input = read_input("input.txt")?;
     let cards = input
         .iter()
         .map(|l| l.parse::<Card>())
         .collect::<Result<Vec<Card>>>()?;
+    for warning in check_ids(&cards)? {
+        eprintln!("{}", warning);
+    }
 
-    let sol1 = cards.iter().map(|c| c.value()).sum::<u32>();
+    let sol1 = cards.iter().map(|c| points.points(c)).sum::<u32>();
     dbg!(sol1);
 
-    let mut copies: Vec<usize> = vec![1; cards.len()];
-    for (i, card) in cards.iter().enumerate() {
-        for j in (i + 1)..(i + 1 + card.score()) {
-            copies[j] += copies[i];
-        }
+    let cascade = Cascade::run(&cards, copies.as_ref());
+    for o in &cascade.overflows {
+        eprintln!("card {} would copy {} missing cards", o.card, o.missing);
     }
 
-    let sol2 = copies.iter().sum::<usize>();
+    let sol2 = cascade.total();
     dbg!(sol2);
 
+    if explain {
+        for c in &cascade.cards {
+            let sources = c
+                .sources
+                .iter()
+                .map(|(from, n)| format!("{} from card {}", n, from))
+                .collect::<Vec<String>>();
+            println!(
+                "card {}: {} copies ({})",
+                c.id,
+                c.copies,
+                sources.join(", ")
+            );
+        }
+        for (round, counts) in cascade.rounds.iter().enumerate() {
+            println!(
+                "round {}: {} copies {:?}",
+                round,
+                counts.iter().sum::<usize>(),
+                counts
+            );
+        }
+    }
+
     Ok(())
 }
 
+// The sets are what scoring uses. `numbers` and `winning` only keep the
+// numbers in the order they were written, which `matches` needs. Neither
+// allocates for puzzle-sized cards.
 struct Card {
-    numbers: HashSet<u32>,
-    winning: HashSet<u32>,
+    id: u32,
+    numbers: Numbers,
+    winning: Numbers,
+    numbers_set: NumberSet,
+    winning_set: NumberSet,
+}
+
+// A card's numbers in input order. Up to `INLINE` numbers below 256 are kept
+// in place as bytes; a longer list or a larger number moves them to the heap.
+#[derive(Debug, PartialEq)]
+enum Numbers {
+    Inline {
+        len: u8,
+        items: [u8; Numbers::INLINE],
+    },
+    Heap(Vec<u32>),
+}
+
+impl Numbers {
+    const INLINE: usize = 32;
+
+    fn new() -> Numbers {
+        Numbers::Inline {
+            len: 0,
+            items: [0; Numbers::INLINE],
+        }
+    }
+
+    fn push(&mut self, n: u32) {
+        match self {
+            Numbers::Inline { len, items } if (*len as usize) < Numbers::INLINE && n <= 255 => {
+                items[*len as usize] = n as u8;
+                *len += 1;
+            }
+            Numbers::Inline { .. } => {
+                let mut heap = self.iter().collect::<Vec<u32>>();
+                heap.push(n);
+                *self = Numbers::Heap(heap);
+            }
+            Numbers::Heap(heap) => heap.push(n),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        let (inline, heap): (&[u8], &[u32]) = match self {
+            Numbers::Inline { len, items } => (&items[..*len as usize], &[]),
+            Numbers::Heap(heap) => (&[], heap),
+        };
+        inline.iter().map(|n| *n as u32).chain(heap.iter().copied())
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Numbers::Inline { .. } => 0,
+            Numbers::Heap(heap) => heap.capacity() * std::mem::size_of::<u32>(),
+        }
+    }
+}
+
+// Puzzle numbers stay below 100, so a card's numbers normally fit in a u128
+// and counting matches is an AND and a popcount. Larger numbers fall back to a
+// regular set.
+#[derive(Debug, PartialEq)]
+enum NumberSet {
+    Bits(u128),
+    General(HashSet<u32>),
+}
+
+impl NumberSet {
+    fn new(numbers: impl IntoIterator<Item = u32>) -> NumberSet {
+        let mut set = NumberSet::Bits(0);
+        for n in numbers {
+            set.insert(n);
+        }
+        set
+    }
+
+    fn insert(&mut self, n: u32) {
+        match self {
+            NumberSet::Bits(bits) if n < u128::BITS => *bits |= 1 << n,
+            NumberSet::Bits(bits) => {
+                let mut set = (0..u128::BITS)
+                    .filter(|i| *bits & 1 << i != 0)
+                    .collect::<HashSet<u32>>();
+                set.insert(n);
+                *self = NumberSet::General(set);
+            }
+            NumberSet::General(set) => {
+                set.insert(n);
+            }
+        }
+    }
+
+    fn contains(&self, n: u32) -> bool {
+        match self {
+            NumberSet::Bits(bits) => n < u128::BITS && bits & 1 << n != 0,
+            NumberSet::General(set) => set.contains(&n),
+        }
+    }
+
+    fn intersection_count(&self, other: &NumberSet) -> usize {
+        match (self, other) {
+            (NumberSet::Bits(a), NumberSet::Bits(b)) => (a & b).count_ones() as usize,
+            (NumberSet::General(set), other) | (other, NumberSet::General(set)) => {
+                set.iter().filter(|n| other.contains(**n)).count()
+            }
+        }
+    }
 }
 
 impl std::str::FromStr for Card {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (_, card_str) = s
+        let (prefix_str, card_str) = s
             .split_once(": ")
             .ok_or(anyhow!("invalid card str: {}", s))?;
+        let id = match prefix_str.split_ascii_whitespace().collect::<Vec<&str>>()[..] {
+            ["Card", id_str] => id_str.parse::<u32>()?,
+            _ => return Err(anyhow!("invalid card str: {}", s)),
+        };
 
         let (numbers_str, winning_str) = card_str
             .split_once(" | ")
             .ok_or(anyhow!("invalid card str: {}", s))?;
 
-        let numbers = numbers_str
-            .split_ascii_whitespace()
-            .map(|n| n.parse().map_err(|e: std::num::ParseIntError| anyhow!(e)))
-            .collect::<Result<HashSet<u32>>>()?;
-        let winning = winning_str
-            .split_ascii_whitespace()
-            .map(|n| n.parse().map_err(|e: std::num::ParseIntError| anyhow!(e)))
-            .collect::<Result<HashSet<u32>>>()?;
+        let numbers = parse_numbers(numbers_str).map_err(|e| anyhow!("card {}: {}", id, e))?;
+        let winning = parse_numbers(winning_str).map_err(|e| anyhow!("card {}: {}", id, e))?;
+
+        Ok(Card {
+            id,
+            numbers_set: NumberSet::new(numbers.iter()),
+            winning_set: NumberSet::new(winning.iter()),
+            numbers,
+            winning,
+        })
+    }
+}
+
+fn parse_numbers(s: &str) -> Result<Numbers> {
+    let mut numbers = Numbers::new();
+    for n_str in s.split_ascii_whitespace() {
+        let n = n_str.parse::<u32>()?;
+        if numbers.iter().any(|m| m == n) {
+            return Err(anyhow!("duplicate number {} in '{}'", n, s.trim()));
+        }
+        numbers.push(n);
+    }
+    Ok(numbers)
+}
 
-        Ok(Card { numbers, winning })
+// Duplicate ids are an error, since the cascade looks cards up by id, while
+// gaps and out-of-order ids are only reported back as warnings.
+fn check_ids(cards: &[Card]) -> Result<Vec<String>> {
+    let mut seen = HashSet::new();
+    let mut warnings = vec![];
+    for (i, card) in cards.iter().enumerate() {
+        if !seen.insert(card.id) {
+            return Err(anyhow!("duplicate card {}", card.id));
+        }
+        let expected = if i == 0 {
+            1
+        } else {
+            cards[i - 1].id as u64 + 1
+        };
+        if card.id as u64 != expected {
+            warnings.push(format!(
+                "card {} found where card {} was expected",
+                card.id, expected
+            ));
+        }
     }
+    Ok(warnings)
 }
 
 impl Card {
     fn score(&self) -> usize {
-        self.numbers.intersection(&self.winning).count()
+        self.numbers_set.intersection_count(&self.winning_set)
+    }
+
+    fn matches(&self) -> impl Iterator<Item = u32> + '_ {
+        self.numbers
+            .iter()
+            .filter(|n| self.winning_set.contains(*n))
     }
+}
+
+trait Points {
+    fn points(&self, card: &Card) -> u32;
+}
 
-    fn value(&self) -> u32 {
-        let score = self.score();
+struct Doubling;
+
+impl Points for Doubling {
+    fn points(&self, card: &Card) -> u32 {
+        let score = card.score();
         if score == 0 {
             return 0;
         }
@@ -71,7 +270,562 @@ impl Card {
     }
 }
 
+struct Linear;
+
+impl Points for Linear {
+    fn points(&self, card: &Card) -> u32 {
+        card.score() as u32
+    }
+}
+
+struct Fibonacci;
+
+impl Points for Fibonacci {
+    fn points(&self, card: &Card) -> u32 {
+        let (mut a, mut b) = (0, 1);
+        for _ in 0..card.score() {
+            (a, b) = (b, a + b);
+        }
+        a
+    }
+}
+
+// Adds `bonus` points on top of `base` for every bonus number the card matches.
+struct Bonus {
+    base: Box<dyn Points>,
+    numbers: HashSet<u32>,
+    bonus: u32,
+}
+
+impl Points for Bonus {
+    fn points(&self, card: &Card) -> u32 {
+        let bonuses = card.matches().filter(|n| self.numbers.contains(n)).count() as u32;
+        self.base.points(card) + bonuses * self.bonus
+    }
+}
+
+trait CopyRule {
+    // Ids of the cards won by `card`, one entry per copy. They may name cards
+    // that don't exist, which the cascade reports as an overflow. They're u64
+    // so that ids past the last possible card can't wrap around.
+    fn targets(&self, card: &Card) -> Vec<u64>;
+}
+
+struct NextCards;
+
+impl CopyRule for NextCards {
+    fn targets(&self, card: &Card) -> Vec<u64> {
+        let id = card.id as u64;
+        (id + 1..=id + card.score() as u64).collect()
+    }
+}
+
+// A winning card wins one copy per match of the single card `offset` ahead.
+// An offset of 0 would have every winning card copy itself forever.
+struct AtOffset(NonZeroU32);
+
+impl CopyRule for AtOffset {
+    fn targets(&self, card: &Card) -> Vec<u64> {
+        vec![card.id as u64 + self.0.get() as u64; card.score()]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct CardCopies {
+    id: u32,
+    copies: usize,
+    sources: Vec<(u32, usize)>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Overflow {
+    card: u32,
+    missing: usize,
+}
+
+// Round 0 holds the original cards, and every later round holds the copies won
+// by the cards of the round before it, so the rounds add up to the final copies.
+struct Cascade {
+    cards: Vec<CardCopies>,
+    overflows: Vec<Overflow>,
+    rounds: Vec<Vec<usize>>,
+}
+
+impl Cascade {
+    fn run(cards: &[Card], rule: &dyn CopyRule) -> Cascade {
+        let positions: HashMap<u32, usize> =
+            cards.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+        let targets = cards
+            .iter()
+            .map(|c| {
+                rule.targets(c)
+                    .into_iter()
+                    .map(|id| {
+                        u32::try_from(id)
+                            .ok()
+                            .and_then(|id| positions.get(&id).copied())
+                    })
+                    .collect()
+            })
+            .collect::<Vec<Vec<Option<usize>>>>();
+
+        let mut copies: Vec<CardCopies> = cards
+            .iter()
+            .map(|c| CardCopies {
+                id: c.id,
+                copies: 1,
+                sources: vec![],
+            })
+            .collect();
+        let mut overflows = vec![];
+        let mut order = (0..cards.len()).collect::<Vec<usize>>();
+        order.sort_by_key(|i| cards[*i].id);
+        for i in order {
+            let card = &cards[i];
+            let missing = targets[i].iter().filter(|j| j.is_none()).count();
+            if missing > 0 {
+                overflows.push(Overflow {
+                    card: card.id,
+                    missing,
+                });
+            }
+            let won = copies[i].copies;
+            for j in targets[i].iter().flatten() {
+                copies[*j].copies += won;
+                match copies[*j].sources.last_mut() {
+                    Some((from, n)) if *from == card.id => *n += won,
+                    _ => copies[*j].sources.push((card.id, won)),
+                }
+            }
+        }
+
+        let mut rounds = vec![vec![1; cards.len()]];
+        loop {
+            let previous = rounds.last().unwrap();
+            let mut next = vec![0; cards.len()];
+            for (i, ts) in targets.iter().enumerate() {
+                for j in ts.iter().flatten() {
+                    next[*j] += previous[i];
+                }
+            }
+            if next.iter().all(|n| *n == 0) {
+                break;
+            }
+            rounds.push(next);
+        }
+
+        Cascade {
+            cards: copies,
+            overflows,
+            rounds,
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.cards.iter().map(|c| c.copies).sum()
+    }
+}
+
+struct Config {
+    explain: bool,
+    points: Box<dyn Points>,
+    copies: Box<dyn CopyRule>,
+    bench: Option<usize>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config> {
+    let mut explain = false;
+    let mut points: Box<dyn Points> = Box::new(Doubling);
+    let mut bonus = None;
+    let mut copies: Box<dyn CopyRule> = Box::new(NextCards);
+    let mut bench = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--explain" => explain = true,
+            "--points" => {
+                points = match args.next().as_deref() {
+                    Some("doubling") => Box::new(Doubling),
+                    Some("linear") => Box::new(Linear),
+                    Some("fibonacci") => Box::new(Fibonacci),
+                    rule => return Err(anyhow!("invalid points rule: {:?}", rule)),
+                }
+            }
+            "--bonus" => {
+                let bonus_str = args.next().ok_or(anyhow!("missing value for --bonus"))?;
+                let (bonus_value, numbers_str) = bonus_str
+                    .split_once(':')
+                    .ok_or(anyhow!("invalid bonus: {}", bonus_str))?;
+                let numbers = numbers_str
+                    .split(',')
+                    .map(|n| n.parse().map_err(|e: std::num::ParseIntError| anyhow!(e)))
+                    .collect::<Result<HashSet<u32>>>()?;
+                bonus = Some((bonus_value.parse::<u32>()?, numbers));
+            }
+            "--copies" => {
+                copies = match args.next().as_deref() {
+                    Some("next") => Box::new(NextCards),
+                    Some(rule) if rule.starts_with("offset:") => {
+                        let offset = rule["offset:".len()..].parse::<u32>()?;
+                        Box::new(AtOffset(
+                            NonZeroU32::new(offset)
+                                .ok_or(anyhow!("copy offset must be at least 1: {}", rule))?,
+                        ))
+                    }
+                    rule => return Err(anyhow!("invalid copy rule: {:?}", rule)),
+                }
+            }
+            "--bench" => {
+                let size_str = args.next().ok_or(anyhow!("missing value for --bench"))?;
+                bench = Some(size_str.parse()?);
+            }
+            _ => return Err(anyhow!("unknown option: {}", arg)),
+        }
+    }
+    if let Some((bonus, numbers)) = bonus {
+        points = Box::new(Bonus {
+            base: points,
+            numbers,
+            bonus,
+        });
+    }
+
+    Ok(Config {
+        explain,
+        points,
+        copies,
+        bench,
+    })
+}
+
+// Builds a deck of `size` cards shaped like the puzzle input (10 numbers and
+// 25 winning numbers below 100), from a fixed-seed LCG so runs are comparable.
+fn generate_deck(size: usize) -> Vec<String> {
+    let mut state: u64 = 0x2023_1204;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) as u32 % 99 + 1
+    };
+    let mut draw = |count: usize| {
+        let mut numbers = vec![];
+        while numbers.len() < count {
+            let n = next();
+            if !numbers.contains(&n) {
+                numbers.push(n);
+            }
+        }
+        numbers
+            .iter()
+            .map(|n| format!("{:2}", n))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    (1..=size)
+        .map(|id| format!("Card {}: {} | {}", id, draw(10), draw(25)))
+        .collect()
+}
+
+// Parses a card the way the old `HashSet` version did, for the benchmark.
+fn parse_hash_sets(s: &str) -> Result<(HashSet<u32>, HashSet<u32>)> {
+    let (_, card_str) = s
+        .split_once(": ")
+        .ok_or(anyhow!("invalid card str: {}", s))?;
+    let (numbers_str, winning_str) = card_str
+        .split_once(" | ")
+        .ok_or(anyhow!("invalid card str: {}", s))?;
+    let parse = |s: &str| {
+        s.split_ascii_whitespace()
+            .map(|n| n.parse().map_err(|e: std::num::ParseIntError| anyhow!(e)))
+            .collect::<Result<HashSet<u32>>>()
+    };
+    Ok((parse(numbers_str)?, parse(winning_str)?))
+}
+
+// Times parsing, which is where each card's allocations happen, and scoring
+// separately for both representations.
+fn run_bench(size: usize) -> Result<()> {
+    let deck = generate_deck(size);
+
+    let start = std::time::Instant::now();
+    let hash_sets = deck
+        .iter()
+        .map(|l| parse_hash_sets(l))
+        .collect::<Result<Vec<_>>>()?;
+    let hash_parse = start.elapsed();
+    let start = std::time::Instant::now();
+    let hash_total = hash_sets
+        .iter()
+        .map(|(n, w)| n.intersection(w).count())
+        .sum::<usize>();
+    let hash_score = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let cards = deck
+        .iter()
+        .map(|l| l.parse::<Card>())
+        .collect::<Result<Vec<Card>>>()?;
+    let bits_parse = start.elapsed();
+    let start = std::time::Instant::now();
+    let bits_total = cards.iter().map(|c| c.score()).sum::<usize>();
+    let bits_score = start.elapsed();
+
+    if hash_total != bits_total {
+        return Err(anyhow!("scores differ: {} != {}", hash_total, bits_total));
+    }
+    // Heap bytes held by the numbers themselves, ignoring allocator and hash
+    // table overhead.
+    let hash_heap = hash_sets
+        .iter()
+        .map(|(n, w)| (n.capacity() + w.capacity()) * std::mem::size_of::<u32>())
+        .sum::<usize>();
+    let bits_heap = cards
+        .iter()
+        .map(|c| c.numbers.heap_bytes() + c.winning.heap_bytes())
+        .sum::<usize>();
+    println!("{} cards, {} matches", size, bits_total);
+    println!(
+        "HashSet: parse {:?}, score {:?}, ~{} heap bytes",
+        hash_parse, hash_score, hash_heap
+    );
+    println!(
+        "bitset:  parse {:?}, score {:?}, ~{} heap bytes",
+        bits_parse, bits_score, bits_heap
+    );
+
+    Ok(())
+}
+
 fn read_input<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<String>> {
     let file = File::open(path)?;
     BufReader::new(file).lines().map(|l| Ok(l?)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_ids, generate_deck, parse_args, read_input, AtOffset, Bonus, Card, Cascade, CopyRule,
+        Doubling, Fibonacci, Linear, NextCards, NumberSet, Numbers, Overflow, Points,
+    };
+    use anyhow::Result;
+    use std::num::NonZeroU32;
+
+    #[test]
+    fn test_cascade() -> Result<()> {
+        let cards = read_input("input-small.txt")?
+            .iter()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+        let cascade = Cascade::run(&cards, &NextCards);
+        assert_eq!(cascade.total(), 30);
+        assert_eq!(
+            cascade
+                .cards
+                .iter()
+                .map(|c| c.copies)
+                .collect::<Vec<usize>>(),
+            vec![1, 2, 4, 8, 14, 1]
+        );
+        assert_eq!(cascade.cards[3].sources, vec![(1, 1), (2, 2), (3, 4)]);
+        assert_eq!(
+            cascade
+                .rounds
+                .iter()
+                .map(|r| r.iter().sum::<usize>())
+                .sum::<usize>(),
+            30
+        );
+        assert_eq!(cascade.rounds[1], vec![0, 1, 2, 3, 3, 0]);
+        assert!(cascade.overflows.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cascade_overflow() -> Result<()> {
+        let cards = ["Card 1: 1 | 2", "Card 2: 1 2 3 | 1 2 3"]
+            .iter()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+        let cascade = Cascade::run(&cards, &NextCards);
+        assert_eq!(cascade.total(), 2);
+        assert_eq!(
+            cascade.overflows,
+            vec![Overflow {
+                card: 2,
+                missing: 3
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_points() -> Result<()> {
+        let card = "Card 1: 1 2 3 4 5 | 1 2 3 4 9".parse::<Card>()?;
+        assert_eq!(Doubling.points(&card), 8);
+        assert_eq!(Linear.points(&card), 4);
+        assert_eq!(Fibonacci.points(&card), 3);
+        let bonus = Bonus {
+            base: Box::new(Linear),
+            numbers: [2, 5, 9].into_iter().collect(),
+            bonus: 10,
+        };
+        assert_eq!(bonus.points(&card), 14);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_at_offset() -> Result<()> {
+        let cards = ["Card 1: 1 2 | 1 2", "Card 2: 1 | 2", "Card 3: 1 | 1"]
+            .iter()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+        let cascade = Cascade::run(&cards, &AtOffset(NonZeroU32::new(2).unwrap()));
+        assert_eq!(
+            cascade
+                .cards
+                .iter()
+                .map(|c| c.copies)
+                .collect::<Vec<usize>>(),
+            vec![1, 1, 3]
+        );
+        assert_eq!(cascade.cards[2].sources, vec![(1, 2)]);
+        assert_eq!(
+            cascade.overflows,
+            vec![Overflow {
+                card: 3,
+                missing: 1
+            }]
+        );
+
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<String>>();
+        assert!(parse_args(args("--copies offset:0").into_iter()).is_err());
+        assert!(parse_args(args("--copies offset:x").into_iter()).is_err());
+        assert!(parse_args(args("--copies offset:3").into_iter()).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_card() -> Result<()> {
+        let card = "Card  12: 41 48 83 | 83 86  6".parse::<Card>()?;
+        assert_eq!(card.id, 12);
+        assert_eq!(card.numbers.iter().collect::<Vec<u32>>(), vec![41, 48, 83]);
+        assert_eq!(card.winning.iter().collect::<Vec<u32>>(), vec![83, 86, 6]);
+        assert_eq!(card.numbers.heap_bytes() + card.winning.heap_bytes(), 0);
+        assert!("Card 1: 41 48 41 | 83".parse::<Card>().is_err());
+        assert!("Crad 1: 41 | 83".parse::<Card>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ids_keyed_cascade() -> Result<()> {
+        let cards = ["Card 1: 1 2 | 1 2", "Card 3: 1 | 1", "Card 2: 1 | 2"]
+            .iter()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+        assert_eq!(
+            check_ids(&cards)?,
+            vec![
+                "card 3 found where card 2 was expected",
+                "card 2 found where card 4 was expected"
+            ]
+        );
+        let cascade = Cascade::run(&cards, &NextCards);
+        assert_eq!(
+            cascade
+                .cards
+                .iter()
+                .map(|c| (c.id, c.copies))
+                .collect::<Vec<(u32, usize)>>(),
+            vec![(1, 1), (3, 2), (2, 2)]
+        );
+        assert_eq!(
+            cascade.overflows,
+            vec![Overflow {
+                card: 3,
+                missing: 1
+            }]
+        );
+
+        let duplicated = ["Card 1: 1 | 1", "Card 1: 1 | 2"]
+            .iter()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+        assert!(check_ids(&duplicated).is_err());
+
+        let last = ["Card 4294967295: 1 | 1", "Card 1: 1 | 2"]
+            .iter()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+        assert_eq!(
+            check_ids(&last)?,
+            vec![
+                "card 4294967295 found where card 1 was expected",
+                "card 1 found where card 4294967296 was expected"
+            ]
+        );
+        for rule in [&NextCards as &dyn CopyRule, &AtOffset(NonZeroU32::MIN)] {
+            let cascade = Cascade::run(&last, rule);
+            assert_eq!(
+                cascade.overflows,
+                vec![Overflow {
+                    card: u32::MAX,
+                    missing: 1
+                }]
+            );
+            assert_eq!(cascade.cards[0].copies, 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_set() -> Result<()> {
+        let small = NumberSet::new([1, 5, 99, 127]);
+        assert!(matches!(small, NumberSet::Bits(_)));
+        assert!(small.contains(127));
+        assert!(!small.contains(128));
+        assert_eq!(small.intersection_count(&NumberSet::new([5, 6, 127])), 2);
+
+        let large = NumberSet::new([5, 127, 128, 1000]);
+        assert!(matches!(large, NumberSet::General(_)));
+        assert_eq!(small.intersection_count(&large), 2);
+        assert_eq!(large.intersection_count(&small), 2);
+        assert_eq!(large.intersection_count(&NumberSet::new([1000, 128, 4])), 2);
+
+        let card = "Card 1: 200 3 | 3 200 7".parse::<Card>()?;
+        assert_eq!(card.score(), 2);
+
+        let card = "Card 2: 4 1000 2 | 2 1000".parse::<Card>()?;
+        assert!(matches!(card.numbers, Numbers::Heap(_)));
+        assert!(matches!(card.winning, Numbers::Heap(_)));
+        assert_eq!(card.numbers.iter().collect::<Vec<u32>>(), vec![4, 1000, 2]);
+        assert_eq!(card.matches().collect::<Vec<u32>>(), vec![1000, 2]);
+        let mut long = Numbers::new();
+        (0..40).rev().for_each(|n| long.push(n));
+        assert!(matches!(long, Numbers::Heap(_)));
+        assert_eq!(
+            long.iter().collect::<Vec<u32>>(),
+            (0..40).rev().collect::<Vec<u32>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generated_deck() -> Result<()> {
+        let cards = generate_deck(50)
+            .iter()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+        assert_eq!(cards.len(), 50);
+        assert!(check_ids(&cards)?.is_empty());
+        for card in &cards {
+            let winning = card
+                .winning
+                .iter()
+                .collect::<std::collections::HashSet<_>>();
+            let expected = card.numbers.iter().filter(|n| winning.contains(n)).count();
+            assert_eq!(card.numbers.heap_bytes() + card.winning.heap_bytes(), 0);
+            assert_eq!(card.score(), expected);
+        }
+        Ok(())
+    }
+}
@@ -1,316 +1,1151 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
 
+#[derive(Debug, PartialEq, Clone)]
 struct Range {
     dst_range_start: u64,
     src_range_start: u64,
     length: u64,
 }
 
+// One past the largest u64, for the end of a range that reaches u64::MAX.
+const END: u128 = u64::MAX as u128 + 1;
+
 impl Range {
+    // Ends are exclusive, so they're u128 to let a range end at u64::MAX.
+    fn src_end(&self) -> u128 {
+        self.src_range_start as u128 + self.length as u128
+    }
+
+    fn dst_end(&self) -> u128 {
+        self.dst_range_start as u128 + self.length as u128
+    }
+
     fn get(&self, src: u64) -> Option<u64> {
-        let delta: i64 = src as i64 - self.src_range_start as i64;
-        if delta >= 0 && delta <= self.length as i64 {
-            Some(self.dst_range_start + delta as u64)
+        let delta = src.checked_sub(self.src_range_start)?;
+        if delta < self.length {
+            self.dst_range_start.checked_add(delta)
         } else {
             None
         }
     }
 }
 
+#[derive(Debug, PartialEq)]
+enum Issue {
+    Overlap {
+        first: usize,
+        second: usize,
+        start: u64,
+        length: u64,
+    },
+    Gap {
+        start: u64,
+        length: u64,
+    },
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::Overlap {
+                first,
+                second,
+                start,
+                length,
+            } => write!(
+                f,
+                "ranges {} and {} overlap on {} values from {}",
+                first, second, length, start
+            ),
+            Issue::Gap { start, length } => {
+                write!(f, "{} values from {} are not covered", length, start)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
 struct Map {
+    from: String,
+    to: String,
+    // Sorted by source start. `positions[i]` is where `ranges[i]` appeared in
+    // the input, so messages can point back at the line.
     ranges: Vec<Range>,
+    positions: Vec<usize>,
 }
 
 impl Map {
-    fn new(ranges: &[(u64, u64, u64)]) -> Map {
+    fn new(from: &str, to: &str, ranges: &[(u64, u64, u64)]) -> Result<Map> {
+        // A range whose last destination doesn't fit in u64 can't be looked
+        // up, and silently falling back to the identity would be wrong.
+        if let Some((d, s, l)) = ranges
+            .iter()
+            .find(|(d, _, l)| d.checked_add(l.saturating_sub(1)).is_none())
+        {
+            return Err(anyhow!(
+                "{}-to-{} map: range {} {} {} maps past {}",
+                from,
+                to,
+                d,
+                s,
+                l,
+                u64::MAX
+            ));
+        }
+        let map = Map::unchecked(from, to, ranges);
+        // Lookups only look at one candidate range, so they'd be wrong.
+        let overlaps = map
+            .check()
+            .into_iter()
+            .filter(|i| matches!(i, Issue::Overlap { .. }))
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>();
+        if !overlaps.is_empty() {
+            return Err(anyhow!("{} map: {}", map.name(), overlaps.join("; ")));
+        }
+        Ok(map)
+    }
+
+    // Sorts the ranges without validating them, so `check` can report on a
+    // table that `new` would reject.
+    fn unchecked(from: &str, to: &str, ranges: &[(u64, u64, u64)]) -> Map {
+        let mut ranges = ranges
+            .iter()
+            .enumerate()
+            .map(|(i, (d, s, l))| {
+                (
+                    i,
+                    Range {
+                        dst_range_start: *d,
+                        src_range_start: *s,
+                        length: *l,
+                    },
+                )
+            })
+            .collect::<Vec<(usize, Range)>>();
+        ranges.sort_by_key(|(_, r)| r.src_range_start);
+        let (positions, ranges) = ranges.into_iter().unzip();
+        Map {
+            from: from.to_string(),
+            to: to.to_string(),
+            ranges,
+            positions,
+        }
+    }
+
+    // For maps built from other maps rather than from input, where the ranges
+    // are already sorted and their positions are just their order.
+    fn from_sorted(from: &str, to: &str, ranges: Vec<Range>) -> Map {
         Map {
-            ranges: ranges
+            from: from.to_string(),
+            to: to.to_string(),
+            positions: (0..ranges.len()).collect(),
+            ranges,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("{}-to-{}", self.from, self.to)
+    }
+
+    // Ranges are sorted by source start and never overlap, so the only
+    // candidate for `src` is the last range starting at or before it.
+    fn get(&self, src: u64) -> u64 {
+        match self.ranges.partition_point(|r| r.src_range_start <= src) {
+            0 => src,
+            i => self.ranges[i - 1].get(src).unwrap_or(src),
+        }
+    }
+
+    fn check(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+        let mut covered_until = 0u128;
+        for (i, r) in self.ranges.iter().enumerate() {
+            if r.src_range_start as u128 > covered_until {
+                issues.push(Issue::Gap {
+                    start: covered_until as u64,
+                    length: (r.src_range_start as u128 - covered_until) as u64,
+                });
+            }
+            for (j, earlier) in self.ranges[..i].iter().enumerate() {
+                if earlier.src_end() > r.src_range_start as u128 {
+                    let (a, b) = (self.positions[j], self.positions[i]);
+                    issues.push(Issue::Overlap {
+                        first: a.min(b),
+                        second: a.max(b),
+                        start: r.src_range_start,
+                        length: (earlier.src_end().min(r.src_end()) - r.src_range_start as u128)
+                            as u64,
+                    });
+                }
+            }
+            covered_until = covered_until.max(r.src_end());
+        }
+        issues
+    }
+
+    // The span of source values covered by at least one range, with an
+    // exclusive end.
+    fn bounds(&self) -> Option<(u64, u128)> {
+        self.ranges
+            .iter()
+            .map(|r| (r.src_range_start, r.src_end()))
+            .reduce(|(lo, hi), (start, end)| (lo.min(start), hi.max(end)))
+    }
+
+    // Splits the interval starting at `start` at the boundaries of the ranges
+    // it crosses. Every piece comes back as the `Range` it's mapped with, along
+    // with the index of the range that matched it; pieces no range covers come
+    // back as identity ranges with no index.
+    fn split(&self, start: u64, length: u64) -> Vec<(Option<usize>, Range)> {
+        self.split_until(start, (start as u128 + length as u128).min(END))
+    }
+
+    // Like `split`, with an exclusive `end` that may be `END`. Pieces always
+    // fit in a `Range`: matched ones are part of a range, and unmatched ones
+    // leave out at least one nonempty range or are shorter than `length`.
+    fn split_until(&self, start: u64, end: u128) -> Vec<(Option<usize>, Range)> {
+        let mut pending = vec![(start as u128, end)];
+        let mut pieces = vec![];
+        for (i, r) in self.ranges.iter().enumerate() {
+            let mut unmatched = vec![];
+            for (start, end) in pending {
+                let overlap_start = start.max(r.src_range_start as u128);
+                let overlap_end = end.min(r.src_end());
+                if overlap_start >= overlap_end {
+                    unmatched.push((start, end));
+                    continue;
+                }
+                pieces.push((
+                    Some(i),
+                    Range {
+                        dst_range_start: r.dst_range_start
+                            + (overlap_start - r.src_range_start as u128) as u64,
+                        src_range_start: overlap_start as u64,
+                        length: (overlap_end - overlap_start) as u64,
+                    },
+                ));
+                if start < overlap_start {
+                    unmatched.push((start, overlap_start));
+                }
+                if overlap_end < end {
+                    unmatched.push((overlap_end, end));
+                }
+            }
+            pending = unmatched;
+        }
+        pieces.extend(pending.into_iter().map(|(start, end)| {
+            (
+                None,
+                Range {
+                    dst_range_start: start as u64,
+                    src_range_start: start as u64,
+                    length: (end - start) as u64,
+                },
+            )
+        }));
+        pieces
+    }
+
+    // Maps whole `(start, length)` intervals at once, so the cost depends on
+    // the number of ranges crossed rather than on the length of the intervals.
+    fn get_ranges(&self, srcs: &[(u64, u64)]) -> Vec<(u64, u64)> {
+        srcs.iter()
+            .flat_map(|(start, length)| self.split(*start, *length))
+            .map(|(_, r)| (r.dst_range_start, r.length))
+            .collect()
+    }
+
+    // Builds the map equivalent to applying `self` and then `next`. Outside the
+    // span of both maps' ranges both are the identity, so only that span needs
+    // to be split; identity pieces are dropped since lookups fall back to them.
+    fn compose(&self, next: &Map) -> Map {
+        let bounds = match (self.bounds(), next.bounds()) {
+            (Some((lo1, hi1)), Some((lo2, hi2))) => Some((lo1.min(lo2), hi1.max(hi2))),
+            (bounds, None) | (None, bounds) => bounds,
+        };
+
+        let mut ranges: Vec<Range> = vec![];
+        if let Some((lo, hi)) = bounds {
+            let mut pieces = self
+                .split_until(lo, hi)
                 .iter()
-                .map(|(d, s, l)| Range {
-                    dst_range_start: *d,
-                    src_range_start: *s,
-                    length: *l,
+                .flat_map(|(_, p)| {
+                    next.split(p.dst_range_start, p.length)
+                        .into_iter()
+                        .map(|(_, q)| Range {
+                            dst_range_start: q.dst_range_start,
+                            src_range_start: p.src_range_start
+                                + (q.src_range_start - p.dst_range_start),
+                            length: q.length,
+                        })
+                        .collect::<Vec<Range>>()
+                })
+                .filter(|r| r.src_range_start != r.dst_range_start)
+                .collect::<Vec<Range>>();
+            pieces.sort_by_key(|r| r.src_range_start);
+            for piece in pieces {
+                match ranges.last_mut() {
+                    Some(last)
+                        if last.src_end() == piece.src_range_start as u128
+                            && last.dst_end() == piece.dst_range_start as u128 =>
+                    {
+                        last.length += piece.length
+                    }
+                    _ => ranges.push(piece),
+                }
+            }
+        }
+
+        Map::from_sorted(&self.from, &next.to, ranges)
+    }
+}
+
+impl Map {
+    // Only a map that permutes the span of its ranges has an inverse: any other
+    // map sends two values to the same place, either through two ranges or
+    // through a range and the identity outside the span.
+    fn invert(&self) -> Result<Map> {
+        let mut ranges = vec![];
+        if let Some((lo, hi)) = self.bounds() {
+            let mut pieces = self
+                .split_until(lo, hi)
+                .into_iter()
+                .map(|(_, p)| p)
+                .collect::<Vec<Range>>();
+            pieces.sort_by_key(|p| p.dst_range_start);
+            let mut expected = lo as u128;
+            for p in &pieces {
+                if p.dst_range_start as u128 != expected {
+                    return Err(anyhow!(
+                        "{} map is not invertible: {} is reached {}",
+                        self.name(),
+                        expected.min(p.dst_range_start as u128),
+                        if p.dst_range_start as u128 > expected {
+                            "through the identity only"
+                        } else {
+                            "more than once"
+                        }
+                    ));
+                }
+                expected = p.dst_end();
+            }
+            if expected != hi {
+                return Err(anyhow!(
+                    "{} map is not invertible: {} is reached through the identity only",
+                    self.name(),
+                    expected.min(hi)
+                ));
+            }
+            ranges = pieces
+                .into_iter()
+                .filter(|p| p.src_range_start != p.dst_range_start)
+                .map(|p| Range {
+                    dst_range_start: p.src_range_start,
+                    src_range_start: p.dst_range_start,
+                    length: p.length,
                 })
-                .collect(),
+                .collect();
         }
+
+        Ok(Map::from_sorted(&self.to, &self.from, ranges))
     }
+}
+
+impl std::fmt::Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} map:", self.name())?;
+        for r in &self.ranges {
+            writeln!(
+                f,
+                "{} {} {}",
+                r.dst_range_start, r.src_range_start, r.length
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct MapChain {
+    maps: Vec<Map>,
+}
+
+#[derive(Debug, PartialEq)]
+struct TraceStep {
+    map: String,
+    src: u64,
+    dst: u64,
+    length: u64,
+    range: Option<usize>,
+}
 
+impl MapChain {
     fn get(&self, src: u64) -> u64 {
-        self.ranges.iter().find_map(|r| r.get(src)).unwrap_or(src)
+        self.maps.iter().fold(src, |value, map| map.get(value))
+    }
+
+    // Follows the values from `start` through every map, splitting them where
+    // `get_ranges` would. `range` is the position of the matching range among
+    // the map's lines in the input, counting from 0, or `None` for the
+    // identity.
+    fn trace(&self, start: u64, length: u64) -> Vec<TraceStep> {
+        let mut steps = vec![];
+        let mut pending = vec![(start, length)];
+        for map in &self.maps {
+            let mut next = vec![];
+            for (start, length) in pending {
+                let mut pieces = map.split(start, length);
+                pieces.sort_by_key(|(_, p)| p.src_range_start);
+                for (range, piece) in pieces {
+                    next.push((piece.dst_range_start, piece.length));
+                    steps.push(TraceStep {
+                        map: map.name(),
+                        src: piece.src_range_start,
+                        dst: piece.dst_range_start,
+                        length: piece.length,
+                        range: range.map(|i| map.positions[i]),
+                    });
+                }
+            }
+            pending = next;
+        }
+        steps
+    }
+
+    fn invert(&self) -> Result<MapChain> {
+        Ok(MapChain {
+            maps: self
+                .maps
+                .iter()
+                .rev()
+                .map(|m| m.invert())
+                .collect::<Result<Vec<Map>>>()?,
+        })
+    }
+
+    // Folds the chain into one map, starting from the identity so even a
+    // single-map chain comes back with sorted, non-overlapping ranges.
+    fn flatten(&self) -> Option<Map> {
+        let first = self.maps.first()?;
+        let identity = Map::from_sorted(&first.from, &first.from, vec![]);
+        Some(self.maps.iter().fold(identity, |acc, map| acc.compose(map)))
     }
 }
 
-fn main() -> Result<()> {
-    let seeds: Vec<u64> = vec![
-        432563865, 39236501, 1476854973, 326201032, 1004521373, 221995697, 2457503679, 46909145,
-        603710475, 11439698, 1242281714, 12935671, 2569215463, 456738587, 3859706369, 129955069,
-        3210146725, 618372750, 601583464, 1413192,
-    ];
-    let seed_ranges: Vec<(u64, u64)> = vec![
-        (432563865, 39236501),
-        (1476854973, 326201032),
-        (1004521373, 221995697),
-        (2457503679, 46909145),
-        (603710475, 11439698),
-        (1242281714, 12935671),
-        (2569215463, 456738587),
-        (3859706369, 129955069),
-        (3210146725, 618372750),
-        (601583464, 1413192),
-    ];
-    let seed_to_soil = Map::new(&[
-        (2824905526, 2969131334, 898611144),
-        (0, 322319732, 9776277),
-        (379216444, 692683038, 140400417),
-        (3723516670, 1559827635, 9493936),
-        (637824014, 332096009, 211964909),
-        (929691047, 1569321571, 35824014),
-        (965515061, 1605145585, 1281263183),
-        (621118546, 833083455, 16705468),
-        (9776277, 0, 322319732),
-        (332096009, 544060918, 47120435),
-        (3733010606, 1319171816, 134731872),
-        (2329500810, 1453903688, 72388062),
-        (2401888872, 1526291750, 33535885),
-        (2246778244, 2886408768, 82722566),
-        (2435424757, 929691047, 389480769),
-        (519616861, 591181353, 101501685),
-    ]);
-    let soil_to_fertilizer = Map::new(&[
-        (2819195624, 2690204780, 252557843),
-        (1098298904, 1339121422, 10546957),
-        (499510245, 852292683, 97183057),
-        (4225167944, 2372810194, 69799352),
-        (887408376, 1808006977, 56225538),
-        (3071753467, 4058417302, 4632491),
-        (2455749676, 3452467754, 363445948),
-        (2243979338, 3846646964, 211770338),
-        (3076385958, 4063049793, 231917503),
-        (3860856553, 2269547568, 103262626),
-        (943633914, 21067281, 39980869),
-        (1388096097, 1584043708, 223963269),
-        (3493291351, 2442609546, 247595234),
-        (1801722612, 949475740, 41203502),
-        (264653767, 678191385, 102026845),
-        (3740886585, 2942762623, 89236706),
-        (1152866424, 310132079, 235229673),
-        (3830123291, 3815913702, 30733262),
-        (249083929, 1568473870, 15569838),
-        (1612059366, 1506703078, 61770792),
-        (366680612, 545361752, 132829633),
-        (1108845861, 1992124969, 44020563),
-        (596693302, 1048406348, 290715074),
-        (3467723121, 2243979338, 25568230),
-        (1879110833, 1349668379, 157034699),
-        (1858043552, 0, 21067281),
-        (3308303461, 3293048094, 159419660),
-        (3964119179, 3031999329, 261048765),
-        (983614783, 780218230, 72074453),
-        (1055689236, 1005796680, 42609668),
-        (1673830158, 1864232515, 127892454),
-        (1842926114, 990679242, 15117438),
-        (0, 61048150, 249083929),
-    ]);
-    let fertilizer_to_water = Map::new(&[
-        (0, 434502471, 470583313),
-        (1739362496, 1919893972, 48874906),
-        (2735409723, 1968768878, 16148586),
-        (3324522082, 1858151799, 61742173),
-        (4137416965, 1984917464, 25502361),
-        (470583313, 2971682591, 186824423),
-        (2751558309, 2532295557, 111781313),
-        (3664705516, 1340674299, 435773845),
-        (1851374390, 3898529330, 80666020),
-        (857422234, 2010419825, 207606184),
-        (1788237402, 2418040507, 63136988),
-        (1065028418, 0, 434502471),
-        (1499530889, 2481177495, 4578648),
-        (4162919326, 2916167530, 55515061),
-        (1670138099, 3158507014, 69224397),
-        (3386264255, 905085784, 278441261),
-        (2496170686, 3979195350, 239239037),
-        (4100479361, 2879229926, 36937604),
-        (1504109537, 3227731411, 166028562),
-        (657407736, 2218026009, 200014498),
-        (2909879036, 3393759973, 257495792),
-        (1932040410, 2644076870, 235153056),
-        (2167193466, 3651255765, 247273565),
-        (2863339622, 2485756143, 46539414),
-        (3167374828, 1183527045, 157147254),
-        (2414467031, 1776448144, 81703655),
-    ]);
-    let water_to_light = Map::new(&[
-        (894548549, 593866955, 6252040),
-        (3168871398, 327816880, 11668092),
-        (3549766643, 2935057349, 16258370),
-        (1070236274, 1304104659, 106353135),
-        (900800589, 723222576, 35881223),
-        (2175309976, 2744985745, 82677694),
-        (3969615926, 3819543751, 36919401),
-        (4086160816, 4015453657, 208806480),
-        (0, 360262147, 233604808),
-        (3566025013, 1514177176, 87956572),
-        (3440195928, 1874477761, 44575925),
-        (1851853101, 948479332, 113304167),
-        (2969520516, 3389635913, 199350882),
-        (1504406289, 1061783499, 137386237),
-        (623007058, 1630426233, 244051528),
-        (1749186436, 620555911, 102666665),
-        (2154532801, 339484972, 20777175),
-        (1965157268, 759103799, 189375533),
-        (4007153882, 3758595179, 60948572),
-        (3180539490, 2951315719, 231363953),
-        (233604808, 1471149229, 43027947),
-        (276632755, 1919053686, 114911374),
-        (1176589409, 0, 327816880),
-        (867058586, 1410457794, 27489963),
-        (391544129, 1199169736, 4581933),
-        (603082303, 600118995, 19924755),
-        (4068102454, 4276290379, 18058362),
-        (3810625421, 3856463152, 158990505),
-        (2969008355, 620043750, 512161),
-        (3758595179, 4224260137, 52030242),
-        (396126062, 3182679672, 206956241),
-        (4006535327, 4294348741, 618555),
-        (3484771853, 3588986795, 64994790),
-        (2257987670, 2033965060, 711020685),
-        (1641792526, 2827663439, 107393910),
-        (3411903443, 1602133748, 28292485),
-        (1037034802, 1437947757, 33201472),
-        (936681812, 1203751669, 100352990),
-    ]);
-    let light_to_temperature = Map::new(&[
-        (1726863959, 864157287, 834947717),
-        (263199301, 190436173, 53620398),
-        (1393417259, 1699105004, 333446700),
-        (2783912856, 244056571, 155961192),
-        (2939874048, 400017763, 299945457),
-        (671449939, 2517852185, 721967320),
-        (2561811676, 2295751005, 222101180),
-        (0, 2032551704, 263199301),
-        (481013766, 0, 190436173),
-        (316819699, 699963220, 164194067),
-    ]);
-    let temperature_to_humidity = Map::new(&[
-        (603287260, 3766826980, 8741130),
-        (572607531, 3684982838, 30679729),
-        (2084038135, 1101548002, 100083930),
-        (655933651, 3228345771, 56278566),
-        (1881393627, 553997241, 168332584),
-        (553997241, 2882185871, 18610290),
-        (627184746, 1072799097, 28748905),
-        (612028390, 3397056204, 15156356),
-        (1693489030, 1430646491, 187904597),
-        (3039118107, 1734352525, 2023479),
-        (220345266, 0, 43042720),
-        (840454312, 3775568110, 147781659),
-        (2184122065, 3715662567, 51164413),
-        (317040325, 171240045, 2422893),
-        (3245373536, 4158663426, 136303870),
-        (145773749, 385599932, 74571517),
-        (0, 43042720, 128197325),
-        (1490020808, 4094893831, 63769595),
-        (319463218, 173662938, 140708231),
-        (712212217, 1821839294, 128242095),
-        (128197325, 314371169, 17576424),
-        (2474405575, 3923349769, 171544062),
-        (2352890439, 3105028111, 121515136),
-        (3467140696, 3318936261, 78119943),
-        (2235286478, 3226543247, 1802524),
-        (4084196651, 722329825, 210770645),
-        (1553790403, 933100470, 139698627),
-        (1261006249, 1201631932, 229014559),
-        (263387986, 331947593, 53652339),
-        (3137034338, 2900796161, 108339198),
-        (988235971, 3412212560, 272770278),
-        (2237089002, 1618551088, 115801437),
-        (3381677406, 1736376004, 85463290),
-        (3545260639, 1950081389, 538936012),
-        (2049726211, 3284624337, 34311924),
-        (2645949637, 2489017401, 393168470),
-        (3041141586, 3009135359, 95892752),
-    ]);
-    let humidity_to_location = Map::new(&[
-        (596652260, 530461632, 95173962),
-        (3845096173, 1731990943, 158117085),
-        (2243878974, 1890108028, 393769632),
-        (0, 625635594, 63651375),
-        (1920725725, 753532949, 155684321),
-        (63651375, 329652856, 200808776),
-        (264460151, 0, 60175490),
-        (1381444473, 2283877660, 346420873),
-        (4003213258, 3594530530, 47694548),
-        (548036821, 60175490, 46076186),
-        (4100105147, 1678246429, 53744514),
-        (2637648606, 3642225078, 292412911),
-        (324635641, 106251676, 223401180),
-        (4050907806, 3545333189, 49197341),
-        (4153849661, 909217270, 141117635),
-        (3455132509, 3477799963, 67533226),
-        (594113007, 689286969, 2539253),
-        (2930061517, 2630298533, 525070992),
-        (753532949, 1050334905, 627911524),
-        (3522665735, 3155369525, 322430438),
-        (2076410046, 3934637989, 167468928),
-        (1727865346, 4102106917, 192860379),
-    ]);
-
-    // let seeds: Vec<u64> = vec![79, 14, 55, 13];
-    // let seed_ranges: Vec<(u64, u64)> = vec![(79, 14), (55, 13)];
-    // let seed_to_soil = Map::new(&[(50, 98, 2), (52, 50, 48)]);
-    // let soil_to_fertilizer = Map::new(&[(0, 15, 37), (37, 52, 2), (39, 0, 15)]);
-    // let fertilizer_to_water = Map::new(&[(49, 53, 8), (0, 11, 42), (42, 0, 7), (57, 7, 4)]);
-    // let water_to_light = Map::new(&[(88, 18, 7), (18, 25, 70)]);
-    // let light_to_temperature = Map::new(&[(45, 77, 23), (81, 45, 19), (68, 64, 13)]);
-    // let temperature_to_humidity = Map::new(&[(0, 69, 1), (1, 0, 69)]);
-    // let humidity_to_location = Map::new(&[(60, 56, 37), (56, 93, 4)]);
-
-    let sol1 = seeds
-        .iter()
-        .map(|seed| {
-            let soil = seed_to_soil.get(*seed);
-            let fertilizer = soil_to_fertilizer.get(soil);
-            let water = fertilizer_to_water.get(fertilizer);
-            let light = water_to_light.get(water);
-            let temperature = light_to_temperature.get(light);
-            let humidity = temperature_to_humidity.get(temperature);
-            humidity_to_location.get(humidity)
+// A map section as written in the input, before any validation.
+struct Table {
+    from: String,
+    to: String,
+    ranges: Vec<(u64, u64, u64)>,
+}
+
+fn parse_tables(lines: &[String]) -> Result<(Vec<u64>, Vec<Table>)> {
+    let mut sections = lines
+        .split(|l| l.trim().is_empty())
+        .filter(|s| !s.is_empty());
+
+    let seeds_str = sections
+        .next()
+        .and_then(|s| s[0].strip_prefix("seeds:"))
+        .ok_or(anyhow!("missing seeds"))?;
+    let seeds = parse_numbers(seeds_str)?;
+
+    let tables = sections
+        .map(|section| {
+            let (from, to) = section[0]
+                .strip_suffix(" map:")
+                .and_then(|h| h.split_once("-to-"))
+                .ok_or(anyhow!("invalid map header: {}", section[0]))?;
+            let ranges = section[1..]
+                .iter()
+                .map(|l| match parse_numbers(l)?[..] {
+                    [d, s, l] => Ok((d, s, l)),
+                    _ => Err(anyhow!("invalid range: {}", l)),
+                })
+                .collect::<Result<Vec<(u64, u64, u64)>>>()?;
+            Ok(Table {
+                from: from.to_string(),
+                to: to.to_string(),
+                ranges,
+            })
         })
-        .min();
-    dbg!(sol1);
+        .collect::<Result<Vec<Table>>>()?;
+    Ok((seeds, tables))
+}
 
-    let new_seeds: Vec<u64> = seed_ranges
+// Checks every table as written, including ones with overlaps that
+// `Almanac::parse` rejects.
+fn check_tables(tables: &[Table]) -> Vec<(Map, Vec<Issue>)> {
+    tables
         .iter()
-        .flat_map(|(s, l)| {
-            std::iter::repeat(s)
-                .zip(0..*l)
-                .map(|(x, y)| x + y)
-                .collect::<Vec<u64>>()
+        .map(|t| {
+            let map = Map::unchecked(&t.from, &t.to, &t.ranges);
+            let issues = map.check();
+            (map, issues)
         })
-        .collect();
+        .collect()
+}
 
-    let len = new_seeds.len();
+struct Almanac {
+    seeds: Vec<u64>,
+    chain: MapChain,
+}
 
-    let sol2 = new_seeds
-        .iter()
-        .enumerate()
-        .inspect(|(i, _)| {
-            if i % 1000000 == 0 {
-                println!("{:.2}", *i as f64 / len as f64 * 100.0)
+impl Almanac {
+    // Reports the problems of every map at once rather than stopping at the
+    // first bad one.
+    fn parse(lines: &[String]) -> Result<Almanac> {
+        let (seeds, tables) = parse_tables(lines)?;
+        let mut maps = vec![];
+        let mut errors = vec![];
+        let mut expected = "seed";
+        for table in &tables {
+            if table.from != expected {
+                errors.push(format!(
+                    "{}-to-{} map doesn't start from {}",
+                    table.from, table.to, expected
+                ));
             }
+            expected = &table.to;
+            match Map::new(&table.from, &table.to, &table.ranges) {
+                Ok(map) => maps.push(map),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!(errors.join("\n")));
+        }
+
+        Ok(Almanac {
+            seeds,
+            chain: MapChain { maps },
         })
-        .map(|(_, seed)| {
-            let soil = seed_to_soil.get(*seed);
-            let fertilizer = soil_to_fertilizer.get(soil);
-            let water = fertilizer_to_water.get(fertilizer);
-            let light = water_to_light.get(water);
-            let temperature = light_to_temperature.get(light);
-            let humidity = temperature_to_humidity.get(temperature);
-            let location = humidity_to_location.get(humidity);
-            location
+    }
+
+    fn seed_ranges(&self) -> Result<Vec<(u64, u64)>> {
+        if !self.seeds.len().is_multiple_of(2) {
+            return Err(anyhow!("odd number of seeds: {}", self.seeds.len()));
+        }
+        Ok(self.seeds.chunks(2).map(|c| (c[0], c[1])).collect())
+    }
+}
+
+fn parse_numbers(s: &str) -> Result<Vec<u64>> {
+    s.split_ascii_whitespace()
+        .map(|n| n.parse().map_err(|e: std::num::ParseIntError| anyhow!(e)))
+        .collect()
+}
+
+// Walks locations upwards from 0 and stops at the first one whose seed falls
+// in one of the seed ranges, using the inverse chain to get back to the seed.
+fn lowest_location_by_reverse_search(
+    inverse: &MapChain,
+    seed_ranges: &[(u64, u64)],
+) -> Option<(u64, u64)> {
+    if seed_ranges.iter().all(|(_, length)| *length == 0) {
+        return None;
+    }
+    (0..=u64::MAX)
+        .map(|location| (location, inverse.get(location)))
+        .find(|(_, seed)| {
+            seed_ranges
+                .iter()
+                .any(|(start, length)| *seed >= *start && *seed - *start < *length)
         })
+}
+
+fn lowest_location_in_range(map: &Map, start: u64, length: u64) -> Option<u64> {
+    map.get_ranges(&[(start, length)])
+        .iter()
+        .filter(|(_, length)| *length > 0)
+        .map(|(start, _)| *start)
+        .min()
+}
+
+// Evaluates the seed ranges across all cores. The bar counts seed ranges and
+// is drawn on stderr, so it never mixes with the answers on stdout.
+fn lowest_location_parallel(map: &Map, seed_ranges: &[(u64, u64)], progress: bool) -> Option<u64> {
+    let bar = if progress {
+        ProgressBar::new(seed_ranges.len() as u64).with_style(
+            ProgressStyle::with_template("{bar:40} {pos}/{len} seed ranges ({per_sec}, eta {eta})")
+                .expect("valid progress template"),
+        )
+    } else {
+        ProgressBar::hidden()
+    };
+    let lowest = seed_ranges
+        .par_iter()
+        .progress_with(bar.clone())
+        .filter_map(|(start, length)| lowest_location_in_range(map, *start, *length))
         .min();
+    bar.finish_and_clear();
+    lowest
+}
+
+fn trace_table(start: u64, length: u64, steps: &[TraceStep]) -> String {
+    let mut out = format!("seeds {}..{}\n", start, start.saturating_add(length));
+    out.push_str(&format!(
+        "{:<28} {:>12} {:>12} {:>12} {:>8}\n",
+        "map", "input", "output", "length", "range"
+    ));
+    for step in steps {
+        let range = step.range.map_or("identity".to_string(), |r| r.to_string());
+        out.push_str(&format!(
+            "{:<28} {:>12} {:>12} {:>12} {:>8}\n",
+            step.map, step.src, step.dst, step.length, range
+        ));
+    }
+    out
+}
+
+fn escape_json(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn trace_json(traces: &[(u64, u64, Vec<TraceStep>)]) -> String {
+    let traces = traces
+        .iter()
+        .map(|(start, length, steps)| {
+            let steps = steps
+                .iter()
+                .map(|step| {
+                    format!(
+                        "{{\"map\":\"{}\",\"input\":{},\"output\":{},\"length\":{},\"range\":{}}}",
+                        escape_json(&step.map),
+                        step.src,
+                        step.dst,
+                        step.length,
+                        step.range.map_or("null".to_string(), |r| r.to_string())
+                    )
+                })
+                .collect::<Vec<String>>();
+            format!(
+                "{{\"start\":{},\"length\":{},\"steps\":[{}]}}",
+                start,
+                length,
+                steps.join(",")
+            )
+        })
+        .collect::<Vec<String>>();
+    format!("[{}]", traces.join(","))
+}
+
+struct Config {
+    check: bool,
+    flatten: bool,
+    reverse_search: bool,
+    seeds_for: Vec<u64>,
+    traces: Vec<(u64, u64)>,
+    json: bool,
+    progress: bool,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config> {
+    let mut config = Config {
+        check: false,
+        flatten: false,
+        reverse_search: false,
+        seeds_for: vec![],
+        traces: vec![],
+        json: false,
+        progress: true,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => config.check = true,
+            "--flatten" => config.flatten = true,
+            "--reverse-search" => config.reverse_search = true,
+            "--seed-for" => {
+                let location_str = args.next().ok_or(anyhow!("missing value for --seed-for"))?;
+                config.seeds_for.push(location_str.parse()?);
+            }
+            "--trace" => {
+                let seeds_str = args.next().ok_or(anyhow!("missing value for --trace"))?;
+                config.traces.push(match seeds_str.split_once(':') {
+                    Some((start, length)) => (start.parse()?, length.parse()?),
+                    None => (seeds_str.parse()?, 1),
+                });
+            }
+            "--json" => config.json = true,
+            "--no-progress" => config.progress = false,
+            _ => return Err(anyhow!("unknown option: {}", arg)),
+        }
+    }
+    Ok(config)
+}
+
+fn main() -> Result<()> {
+    let config = parse_args(std::env::args().skip(1))?;
+    let input = read_input("input.txt")?;
+    if config.check {
+        let (_, tables) = parse_tables(&input)?;
+        for (map, issues) in check_tables(&tables) {
+            println!(
+                "{} map: {} ranges, {} issues",
+                map.name(),
+                map.ranges.len(),
+                issues.len()
+            );
+            for issue in issues {
+                println!("  {}", issue);
+            }
+        }
+    }
+    let almanac = Almanac::parse(&input)?;
+    if !config.traces.is_empty() {
+        let traces = config
+            .traces
+            .iter()
+            .map(|(start, length)| (*start, *length, almanac.chain.trace(*start, *length)))
+            .collect::<Vec<_>>();
+        if config.json {
+            println!("{}", trace_json(&traces));
+        } else {
+            for (start, length, steps) in &traces {
+                print!("{}", trace_table(*start, *length, steps));
+            }
+        }
+    }
+    let map = almanac
+        .chain
+        .flatten()
+        .ok_or(anyhow!("almanac has no maps"))?;
+    if config.flatten {
+        print!("{}", map);
+    }
+
+    let sol1 = almanac.seeds.iter().map(|s| map.get(*s)).min();
+    dbg!(sol1);
+
+    let sol2 = lowest_location_parallel(&map, &almanac.seed_ranges()?, config.progress);
     dbg!(sol2);
 
+    if config.reverse_search || !config.seeds_for.is_empty() {
+        let inverse = almanac.chain.invert()?;
+        for location in &config.seeds_for {
+            println!(
+                "location {} comes from seed {}",
+                location,
+                inverse.get(*location)
+            );
+        }
+        if config.reverse_search {
+            let sol2_reverse = lowest_location_by_reverse_search(&inverse, &almanac.seed_ranges()?);
+            dbg!(sol2_reverse);
+        }
+    }
+
     Ok(())
 }
+
+fn read_input<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<String>> {
+    let file = File::open(path)?;
+    BufReader::new(file).lines().map(|l| Ok(l?)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_tables, lowest_location_by_reverse_search, lowest_location_in_range,
+        lowest_location_parallel, parse_tables, read_input, trace_json, Almanac, Issue, Map,
+        MapChain, Range, TraceStep,
+    };
+    use anyhow::Result;
+
+    #[test]
+    fn test_parse_almanac() -> Result<()> {
+        let almanac = Almanac::parse(&read_input("input-small.txt")?)?;
+        assert_eq!(almanac.seeds, vec![79, 14, 55, 13]);
+        assert_eq!(almanac.seed_ranges()?, vec![(79, 14), (55, 13)]);
+        assert_eq!(
+            almanac
+                .chain
+                .maps
+                .iter()
+                .map(|m| m.to.as_str())
+                .collect::<Vec<&str>>(),
+            vec![
+                "soil",
+                "fertilizer",
+                "water",
+                "light",
+                "temperature",
+                "humidity",
+                "location"
+            ]
+        );
+        assert_eq!(almanac.chain.maps[1].ranges.len(), 3);
+        assert_eq!(
+            almanac.seeds.iter().map(|s| almanac.chain.get(*s)).min(),
+            Some(35)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_get() {
+        let range = Range {
+            dst_range_start: 50,
+            src_range_start: 98,
+            length: 2,
+        };
+        assert_eq!(range.get(97), None);
+        assert_eq!(range.get(98), Some(50));
+        assert_eq!(range.get(99), Some(51));
+        assert_eq!(range.get(100), None);
+
+        let range = Range {
+            dst_range_start: 0,
+            src_range_start: u64::MAX - 1,
+            length: 2,
+        };
+        assert_eq!(range.get(u64::MAX), Some(1));
+        assert_eq!(range.get(0), None);
+
+        // The last destination has to fit, or the map can't be built.
+        assert!(Map::new("a", "b", &[(u64::MAX, 0, 2)]).is_err());
+        assert!(Map::new("a", "b", &[(u64::MAX - 1, 0, 2)]).is_ok());
+        assert_eq!(
+            Map::new("a", "b", &[(u64::MAX, 0, 1)]).unwrap().get(0),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_map_get() -> Result<()> {
+        let map = Map::new("a", "b", &[(50, 98, 2), (52, 50, 48)])?;
+        assert_eq!(map.get(49), 49);
+        assert_eq!(map.get(50), 52);
+        assert_eq!(map.get(97), 99);
+        assert_eq!(map.get(98), 50);
+        assert_eq!(map.get(99), 51);
+        assert_eq!(map.get(100), 100);
+
+        let unsorted = Map::new("a", "b", &[(100, 12, 5), (0, 10, 2)])?;
+        assert_eq!(unsorted.get(9), 9);
+        assert_eq!(unsorted.get(10), 0);
+        assert_eq!(unsorted.get(12), 100);
+        assert_eq!(unsorted.get(16), 104);
+        assert_eq!(unsorted.get(17), 17);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check() -> Result<()> {
+        let map = Map::new("a", "b", &[(0, 20, 5), (0, 10, 5)])?;
+        assert_eq!(
+            map.check(),
+            vec![
+                Issue::Gap {
+                    start: 0,
+                    length: 10
+                },
+                Issue::Gap {
+                    start: 15,
+                    length: 5
+                },
+            ]
+        );
+
+        // Overlaps are reported by input position, not sorted position.
+        let err = Map::new("a", "b", &[(0, 500, 10), (0, 10, 5), (0, 12, 5)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a-to-b map: ranges 1 and 2 overlap on 3 values from 12"
+        );
+        let err =
+            Map::new("a", "b", &[(0, 20, 5), (0, 10, 5), (0, 12, 5), (0, 14, 2)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a-to-b map: ranges 1 and 2 overlap on 3 values from 12; \
+             ranges 1 and 3 overlap on 1 values from 14; \
+             ranges 2 and 3 overlap on 2 values from 14"
+        );
+        // Without the check, `get(15)` would only see the range from 100.
+        assert!(Map::new("a", "b", &[(0, 10, 10), (100, 12, 2)]).is_err());
+
+        // Every stage is checked, not just the first one with an overlap.
+        let lines = "seeds: 1 2\n\nseed-to-soil map:\n0 10 5\n0 12 5\n\n\
+                     soil-to-water map:\n1 1 1\n\n\
+                     water-to-light map:\n0 0 10\n50 5 1"
+            .lines()
+            .map(|l| l.trim().to_string())
+            .collect::<Vec<String>>();
+        let (_, tables) = parse_tables(&lines)?;
+        let overlaps = check_tables(&tables)
+            .iter()
+            .map(|(map, issues)| {
+                let overlaps = issues
+                    .iter()
+                    .filter(|i| matches!(i, Issue::Overlap { .. }))
+                    .count();
+                (map.name(), overlaps)
+            })
+            .collect::<Vec<(String, usize)>>();
+        assert_eq!(
+            overlaps,
+            vec![
+                ("seed-to-soil".to_string(), 1),
+                ("soil-to-water".to_string(), 0),
+                ("water-to-light".to_string(), 1)
+            ]
+        );
+        let err = Almanac::parse(&lines).err().unwrap().to_string();
+        assert_eq!(
+            err,
+            "seed-to-soil map: ranges 0 and 1 overlap on 3 values from 12\n\
+             water-to-light map: ranges 0 and 1 overlap on 1 values from 5"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_ranges_matches_get() -> Result<()> {
+        let almanac = Almanac::parse(&read_input("input-small.txt")?)?;
+        for map in &almanac.chain.maps {
+            let mut expected = (0..120).map(|v| map.get(v)).collect::<Vec<u64>>();
+            expected.sort();
+            let mut actual = map
+                .get_ranges(&[(0, 120)])
+                .iter()
+                .flat_map(|(s, l)| *s..*s + *l)
+                .collect::<Vec<u64>>();
+            actual.sort();
+            assert_eq!(actual, expected, "{}", map.name());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_ranges() -> Result<()> {
+        let map = Map::new("a", "b", &[(50, 98, 2), (52, 50, 48)])?;
+        let mut ranges = map.get_ranges(&[(45, 10), (97, 5)]);
+        ranges.sort();
+        assert_eq!(ranges, vec![(45, 5), (50, 2), (52, 5), (99, 1), (100, 2)]);
+        assert_eq!(map.get_ranges(&[(0, 0)]), vec![(0, 0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_location_ranges() -> Result<()> {
+        let almanac = Almanac::parse(&read_input("input-small.txt")?)?;
+        let ranges = almanac
+            .chain
+            .maps
+            .iter()
+            .fold(almanac.seed_ranges()?, |ranges, m| m.get_ranges(&ranges));
+        assert_eq!(ranges.iter().map(|(s, _)| *s).min(), Some(46));
+        assert_eq!(ranges.iter().map(|(_, l)| *l).sum::<u64>(), 27);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compose() -> Result<()> {
+        let a = Map::new("a", "b", &[(50, 98, 2), (52, 50, 48)])?;
+        let b = Map::new("b", "c", &[(0, 15, 37), (37, 52, 2), (39, 0, 15)])?;
+        let ab = a.compose(&b);
+        assert_eq!(ab.name(), "a-to-c");
+        for v in 0..120 {
+            assert_eq!(ab.get(v), b.get(a.get(v)), "{}", v);
+        }
+        assert!(ab
+            .ranges
+            .windows(2)
+            .all(|w| w[0].src_range_start + w[0].length <= w[1].src_range_start));
+
+        let shift = Map::new("a", "a", &[(10, 0, 10)])?;
+        let back = Map::new("a", "a", &[(0, 10, 10)])?;
+        assert_eq!(
+            shift.compose(&back).ranges,
+            vec![Range {
+                dst_range_start: 0,
+                src_range_start: 10,
+                length: 10
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten() -> Result<()> {
+        let almanac = Almanac::parse(&read_input("input-small.txt")?)?;
+        let map = almanac.chain.flatten().unwrap();
+        assert_eq!(map.name(), "seed-to-location");
+        for v in 0..120 {
+            assert_eq!(
+                map.get(v),
+                almanac.chain.maps.iter().fold(v, |v, m| m.get(v))
+            );
+        }
+        assert_eq!(
+            map.get_ranges(&almanac.seed_ranges()?)
+                .iter()
+                .map(|(s, _)| *s)
+                .min(),
+            Some(46)
+        );
+        assert!(map.to_string().starts_with("seed-to-location map:\n"));
+        assert_eq!(MapChain { maps: vec![] }.flatten().map(|m| m.name()), None);
+        let single = MapChain {
+            maps: vec![Map::new("a", "b", &[(100, 12, 5), (0, 10, 2)])?],
+        };
+        let flattened = single.flatten().unwrap();
+        assert_eq!(flattened.name(), "a-to-b");
+        assert_eq!(flattened.ranges.len(), 2);
+        assert_eq!(flattened.get(15), 103);
+
+        // Ranges reaching the top of u64, on either side of the map.
+        let edges = MapChain {
+            maps: vec![
+                Map::new("a", "b", &[(u64::MAX, 0, 1), (5, 1, 1)])?,
+                Map::new("b", "c", &[])?,
+                Map::new("c", "d", &[(0, u64::MAX - 1, 2)])?,
+            ],
+        };
+        let flattened = edges.flatten().unwrap();
+        for v in [0, 1, 2, 5, u64::MAX - 2, u64::MAX - 1, u64::MAX] {
+            assert_eq!(flattened.get(v), edges.get(v), "{}", v);
+        }
+        let inverse =
+            Map::new("a", "b", &[(0, u64::MAX - 1, 2), (u64::MAX - 1, 0, 2)])?.invert()?;
+        assert_eq!(inverse.get(0), u64::MAX - 1);
+        assert_eq!(inverse.get(u64::MAX), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invert() -> Result<()> {
+        let map = Map::new("a", "b", &[(50, 98, 2), (52, 50, 48)])?;
+        let inverse = map.invert()?;
+        assert_eq!(inverse.name(), "b-to-a");
+        for v in 0..120 {
+            assert_eq!(inverse.get(map.get(v)), v);
+        }
+
+        let collapsing = Map::new("a", "b", &[(0, 10, 5), (0, 20, 5)])?;
+        assert!(collapsing.invert().is_err());
+        let escaping = Map::new("a", "b", &[(100, 0, 10)])?;
+        assert!(escaping.invert().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reverse_search() -> Result<()> {
+        let almanac = Almanac::parse(&read_input("input-small.txt")?)?;
+        let inverse = almanac.chain.invert()?;
+        for seed in 0..120 {
+            assert_eq!(inverse.get(almanac.chain.get(seed)), seed);
+        }
+        assert_eq!(inverse.get(35), 13);
+        assert_eq!(
+            lowest_location_by_reverse_search(&inverse, &almanac.seed_ranges()?),
+            Some((46, 82))
+        );
+        assert_eq!(lowest_location_by_reverse_search(&inverse, &[]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lowest_location_parallel() -> Result<()> {
+        let almanac = Almanac::parse(&read_input("input-small.txt")?)?;
+        let map = almanac.chain.flatten().unwrap();
+        let seed_ranges = almanac.seed_ranges()?;
+        assert_eq!(
+            lowest_location_parallel(&map, &seed_ranges, false),
+            Some(46)
+        );
+
+        // Many generated ranges: the parallel minimum matches a sequential pass.
+        let mut x = 12345u64;
+        let seed_ranges = (0..500)
+            .map(|_| {
+                x = x
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (x % 200, (x >> 32) % 20)
+            })
+            .collect::<Vec<(u64, u64)>>();
+        let sequential = seed_ranges
+            .iter()
+            .filter_map(|(start, length)| lowest_location_in_range(&map, *start, *length))
+            .min();
+        assert_eq!(
+            lowest_location_parallel(&map, &seed_ranges, false),
+            sequential
+        );
+        assert_eq!(lowest_location_parallel(&map, &[], false), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace() -> Result<()> {
+        let almanac = Almanac::parse(&read_input("input-small.txt")?)?;
+        let steps = almanac.chain.trace(79, 1);
+        assert_eq!(
+            steps.iter().map(|s| s.dst).collect::<Vec<u64>>(),
+            vec![81, 81, 81, 74, 78, 78, 82]
+        );
+        assert_eq!(
+            steps[0],
+            TraceStep {
+                map: "seed-to-soil".to_string(),
+                src: 79,
+                dst: 81,
+                length: 1,
+                range: Some(1),
+            }
+        );
+        assert_eq!(steps[1].range, None);
+
+        let steps = almanac.chain.trace(95, 5);
+        assert_eq!(
+            steps
+                .iter()
+                .take_while(|s| s.map == "seed-to-soil")
+                .map(|s| (s.src, s.length, s.range))
+                .collect::<Vec<_>>(),
+            vec![(95, 3, Some(1)), (98, 2, Some(0))]
+        );
+
+        let json = trace_json(&[(79, 1, almanac.chain.trace(79, 1))]);
+        assert!(json.starts_with(
+            "[{\"start\":79,\"length\":1,\"steps\":[{\"map\":\"seed-to-soil\",\"input\":79,\"output\":81,\"length\":1,\"range\":1},{\"map\":\"soil-to-fertilizer\",\"input\":81,\"output\":81,\"length\":1,\"range\":null}"
+        ));
+
+        let step = TraceStep {
+            map: "a\"b\\c\td".to_string(),
+            src: 1,
+            dst: 2,
+            length: 3,
+            range: None,
+        };
+        assert_eq!(
+            trace_json(&[(1, 3, vec![step])]),
+            "[{\"start\":1,\"length\":3,\"steps\":[{\"map\":\"a\\\"b\\\\c\\u0009d\",\"input\":1,\"output\":2,\"length\":3,\"range\":null}]}]"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_almanac_errors() {
+        let parse = |s: &str| Almanac::parse(&s.lines().map(|l| l.to_string()).collect::<Vec<_>>());
+        assert!(parse("seed: 1 2").is_err());
+        assert!(parse("seeds: 1 2\n\nsoil-to-water map:\n1 2 3").is_err());
+        assert!(parse("seeds: 1 2\n\nseed-to-soil map:\n1 2").is_err());
+        assert!(parse("seeds: 1 2 3").and_then(|a| a.seed_ranges()).is_err());
+    }
+}
@@ -1,21 +1,712 @@
-fn main() {
-    // Time:        63     78     94     68
-    // Distance:   411   1274   2047   1035
-
-    let mut sol1: u64 = 1;
-    sol1 *= sols(63, 411);
-    sol1 *= sols(78, 1274);
-    sol1 *= sols(94, 2047);
-    sol1 *= sols(68, 1035);
+use anyhow::{anyhow, Result};
+use num_bigint::BigUint;
+use num_integer::{Integer, Roots};
+use num_traits::{CheckedMul, Unsigned};
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+
+#[derive(Debug, PartialEq)]
+struct Race<T = u64> {
+    time: T,
+    distance: T,
+}
+
+impl Race<BigUint> {
+    fn narrow<'a, T>(&'a self) -> Result<Race<T>>
+    where
+        T: TryFrom<&'a BigUint>,
+    {
+        let narrow = |value: &'a BigUint, what: &str| {
+            T::try_from(value).map_err(|_| {
+                anyhow!(
+                    "{} {} does not fit in {}",
+                    what,
+                    value,
+                    std::any::type_name::<T>()
+                )
+            })
+        };
+        Ok(Race {
+            time: narrow(&self.time, "time")?,
+            distance: narrow(&self.distance, "distance")?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Sheet {
+    races: Vec<Race>,
+    // The single race you get by ignoring the spaces between the numbers.
+    merged: Race<BigUint>,
+}
+
+impl Sheet {
+    fn parse(lines: &[String]) -> Result<Sheet> {
+        let mut lines = lines.iter().filter(|l| !l.trim().is_empty());
+        let times = parse_row(lines.next(), "Time")?;
+        let distances = parse_row(lines.next(), "Distance")?;
+        if let Some(line) = lines.next() {
+            return Err(anyhow!("unexpected line after race sheet: {}", line));
+        }
+        if times.len() != distances.len() {
+            return Err(anyhow!(
+                "{} times but {} distances",
+                times.len(),
+                distances.len()
+            ));
+        }
+        let races = times
+            .iter()
+            .zip(&distances)
+            .map(|(time, distance)| {
+                Ok(Race {
+                    time: time.parse()?,
+                    distance: distance.parse()?,
+                })
+            })
+            .collect::<Result<Vec<Race>>>()?;
+        let merged = Race {
+            time: times.concat().parse()?,
+            distance: distances.concat().parse()?,
+        };
+        Ok(Sheet { races, merged })
+    }
+}
+
+fn parse_row<'a>(line: Option<&'a String>, label: &str) -> Result<Vec<&'a str>> {
+    let line = line.ok_or(anyhow!("missing {} line", label))?;
+    let (name, values) = line
+        .split_once(':')
+        .ok_or(anyhow!("missing ':' in line: {}", line))?;
+    if name.trim() != label {
+        return Err(anyhow!("expected {} line, got: {}", label, line));
+    }
+    let values = values.split_ascii_whitespace().collect::<Vec<&str>>();
+    if let Some(v) = values
+        .iter()
+        .find(|v| !v.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(anyhow!("invalid {} value: {}", label.to_lowercase(), v));
+    }
+    if values.is_empty() {
+        return Err(anyhow!("no values in {} line", label));
+    }
+    Ok(values)
+}
+
+#[derive(Debug, PartialEq)]
+struct Report {
+    time: u64,
+    record: u64,
+    holds: Option<(u64, u64)>,
+    ways: u64,
+    best_hold: u64,
+    best_distance: u128,
+    // How far the best hold beats the record by, negative if it can't.
+    margin: i128,
+}
+
+impl Report {
+    fn new(model: &dyn Model, race: &Race) -> Report {
+        let holds = model.holds(race);
+        let best_hold = model.peak(race.time);
+        let best_distance = model.distance(best_hold, race.time);
+        Report {
+            time: race.time,
+            record: race.distance,
+            holds,
+            ways: holds.map_or(0, |(min, max)| max - min + 1),
+            best_hold,
+            best_distance,
+            margin: best_distance as i128 - race.distance as i128,
+        }
+    }
+}
+
+fn report_table(reports: &[(String, Report)]) -> String {
+    let mut out = format!(
+        "{:<8} {:>12} {:>16} {:>12} {:>12} {:>12} {:>12} {:>20} {:>20}\n",
+        "race",
+        "time",
+        "record",
+        "ways",
+        "min hold",
+        "max hold",
+        "best hold",
+        "best distance",
+        "margin"
+    );
+    for (name, r) in reports {
+        let (min, max) = r
+            .holds
+            .map_or(("-".to_string(), "-".to_string()), |(min, max)| {
+                (min.to_string(), max.to_string())
+            });
+        out.push_str(&format!(
+            "{:<8} {:>12} {:>16} {:>12} {:>12} {:>12} {:>12} {:>20} {:>20}\n",
+            name, r.time, r.record, r.ways, min, max, r.best_hold, r.best_distance, r.margin
+        ));
+    }
+    out
+}
+
+struct Config {
+    model: Box<dyn Model>,
+    report: bool,
+}
+
+fn parse_model(s: &str) -> Result<Box<dyn Model>> {
+    let (name, value) = s.split_once(':').unwrap_or((s, ""));
+    Ok(match name {
+        "linear" => Box::new(Linear),
+        "capped" => Box::new(Capped(value.parse()?)),
+        "decay" => Box::new(Decay(value.parse()?)),
+        // piecewise:RATExMS,...,RATE, e.g. piecewise:3x10,1
+        "piecewise" => {
+            let mut rates = value.split(',').collect::<Vec<&str>>();
+            let tail = rates.pop().unwrap_or_default().parse()?;
+            let steps = rates
+                .iter()
+                .map(|step| {
+                    let (rate, ms) = step
+                        .split_once('x')
+                        .ok_or(anyhow!("invalid piecewise step: {}", step))?;
+                    Ok((ms.parse()?, rate.parse()?))
+                })
+                .collect::<Result<Vec<(u64, u64)>>>()?;
+            Box::new(Piecewise::new(steps, tail)?)
+        }
+        _ => return Err(anyhow!("invalid model: {}", s)),
+    })
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config> {
+    let mut model: Box<dyn Model> = Box::new(Linear);
+    let mut report = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--model" => {
+                let model_str = args.next().ok_or(anyhow!("missing value for --model"))?;
+                model = parse_model(&model_str)?;
+            }
+            "--report" => report = true,
+            _ => return Err(anyhow!("unknown option: {}", arg)),
+        }
+    }
+    Ok(Config { model, report })
+}
+
+fn main() -> Result<()> {
+    let config = parse_args(std::env::args().skip(1))?;
+    let input = read_input("input.txt")?;
+    let sheet = Sheet::parse(&input)?;
+    if config.report {
+        let mut reports = sheet
+            .races
+            .iter()
+            .enumerate()
+            .map(|(i, r)| ((i + 1).to_string(), Report::new(config.model.as_ref(), r)))
+            .collect::<Vec<(String, Report)>>();
+        // Reports are computed in u64, so a merged race that doesn't fit gets
+        // a note under the table instead of a row.
+        let merged = sheet.merged.narrow();
+        if let Ok(merged) = &merged {
+            reports.push((
+                "merged".to_string(),
+                Report::new(config.model.as_ref(), merged),
+            ));
+        }
+        print!("{}", report_table(&reports));
+        if let Err(e) = merged {
+            println!("merged race not reported: {}", e);
+        }
+    }
+
+    let sol1 = sheet
+        .races
+        .iter()
+        .map(|r| config.model.ways(r))
+        .product::<u64>();
     dbg!(sol1);
 
-    let sol2 = sols(63789468, 411127420471035);
+    let sol2 = config.model.merged_ways(&sheet.merged)?;
     dbg!(sol2);
+
+    Ok(())
+}
+
+fn read_input<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<String>> {
+    let file = File::open(path)?;
+    BufReader::new(file).lines().map(|l| Ok(l?)).collect()
+}
+
+// Counts the hold times `h` in `0..=time` with `h * (time - h) > distance`.
+fn sols<T>(time: &T, distance: &T) -> T
+where
+    T: Integer + Unsigned + Roots + CheckedMul + Clone,
+{
+    holds(time, distance).map_or(T::zero(), |(min, max)| max - min + T::one())
+}
+
+// The shortest and longest winning holds, if there are any. The distance is
+// symmetric around `time / 2`, so it's enough to find the shortest winning
+// hold: the integer square root of the discriminant gives a guess that is at
+// most one off, which the correction steps fix up. When the discriminant
+// doesn't fit in `T` the hold is found by binary search instead.
+fn holds<T>(time: &T, distance: &T) -> Option<(T, T)>
+where
+    T: Integer + Unsigned + Roots + CheckedMul + Clone,
+{
+    let two = T::one() + T::one();
+    // Compares by division so the product can't overflow.
+    let wins = |hold: &T| {
+        let rest = time.clone() - hold.clone();
+        !rest.is_zero() && *hold > distance.clone() / rest
+    };
+    let half = time.clone() / two.clone();
+    if !wins(&half) {
+        return None;
+    }
+    let guess = time
+        .checked_mul(time)
+        .zip(
+            two.checked_mul(&two)
+                .and_then(|four| four.checked_mul(distance)),
+        )
+        .map(|(square, four_distance)| {
+            (time.clone() - (square - four_distance).sqrt()) / two.clone()
+        });
+    let mut min = match guess {
+        Some(guess) => guess,
+        None => {
+            // Holding for 0 never wins and holding for `half` does.
+            let (mut lo, mut hi) = (T::zero(), half);
+            while hi.clone() - lo.clone() > T::one() {
+                let mid = lo.clone() + (hi.clone() - lo.clone()) / two.clone();
+                if wins(&mid) {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+            hi
+        }
+    };
+    while !min.is_zero() && wins(&(min.clone() - T::one())) {
+        min = min - T::one();
+    }
+    while !wins(&min) {
+        min = min + T::one();
+    }
+    let max = time.clone() - min.clone();
+    Some((min, max))
+}
+
+trait Model {
+    // Distance covered by holding the button for `hold` ms of a `time` ms
+    // race. For a fixed `time` it must rise to a single peak and then fall,
+    // so that the winning holds form one interval.
+    fn distance(&self, hold: u64, time: u64) -> u128;
+
+    // The hold that goes furthest. The earliest one if several tie.
+    fn peak(&self, time: u64) -> u64 {
+        first_hold(0, time, |h| {
+            self.distance(h + 1, time) <= self.distance(h, time)
+        })
+    }
+
+    fn holds(&self, race: &Race) -> Option<(u64, u64)> {
+        search_holds(self, race)
+    }
+
+    fn ways(&self, race: &Race) -> u64 {
+        self.holds(race).map_or(0, |(min, max)| max - min + 1)
+    }
+
+    // Ways to win the merged race, which only some models can handle beyond
+    // `u64`.
+    fn merged_ways(&self, race: &Race<BigUint>) -> Result<BigUint> {
+        Ok(self.ways(&race.narrow()?).into())
+    }
 }
 
-fn sols(time: u64, distance: u64) -> u64 {
-    let delta = time.pow(2) - 4 * distance;
-    let min = ((time as f64 - (delta as f64).sqrt()) as f64 / 2.0 + 0.0001).ceil() as u64;
-    let max = ((time as f64 + (delta as f64).sqrt()) as f64 / 2.0 - 0.0001).floor() as u64;
-    max - min + 1
+// Speed grows by 1 mm/ms for every ms the button is held.
+struct Linear;
+
+impl Model for Linear {
+    fn distance(&self, hold: u64, time: u64) -> u128 {
+        hold as u128 * time.saturating_sub(hold) as u128
+    }
+
+    fn peak(&self, time: u64) -> u64 {
+        time / 2
+    }
+
+    fn holds(&self, race: &Race) -> Option<(u64, u64)> {
+        holds(&race.time, &race.distance)
+    }
+
+    // Uses the narrowest type the merged race fits in.
+    fn merged_ways(&self, race: &Race<BigUint>) -> Result<BigUint> {
+        Ok(if let Ok(race) = race.narrow::<u64>() {
+            sols(&race.time, &race.distance).into()
+        } else if let Ok(race) = race.narrow::<u128>() {
+            sols(&race.time, &race.distance).into()
+        } else {
+            sols(&race.time, &race.distance)
+        })
+    }
+}
+
+// Like `Linear`, but the speed stops growing at `cap` mm/ms.
+struct Capped(u64);
+
+impl Model for Capped {
+    fn distance(&self, hold: u64, time: u64) -> u128 {
+        hold.min(self.0) as u128 * time.saturating_sub(hold) as u128
+    }
+
+    fn peak(&self, time: u64) -> u64 {
+        self.0.min(time / 2)
+    }
+
+    // Holds up to the cap are the linear quadratic, and holds past it win
+    // while `cap * (time - hold) > distance`.
+    fn holds(&self, race: &Race) -> Option<(u64, u64)> {
+        let cap = self.0;
+        if cap == 0 {
+            return None;
+        }
+        let below = holds(&race.time, &race.distance)
+            .filter(|(min, _)| *min <= cap)
+            .map(|(min, max)| (min, max.min(cap)));
+        let rest = race.distance / cap + 1;
+        let above =
+            (race.time >= rest && race.time - rest > cap).then(|| (cap + 1, race.time - rest));
+        match (below, above) {
+            (Some((min, _)), Some((_, max))) => Some((min, max)),
+            (below, above) => below.or(above),
+        }
+    }
+}
+
+// Like `Linear`, but the boat loses `rate` mm/ms of speed for every ms it
+// moves, and stops once the speed reaches 0.
+struct Decay(u64);
+
+impl Model for Decay {
+    fn distance(&self, hold: u64, time: u64) -> u128 {
+        let (speed, moving, rate) = (
+            hold as u128,
+            time.saturating_sub(hold) as u128,
+            self.0 as u128,
+        );
+        // Only the first `steps` ms of the run move the boat at all.
+        let steps = match rate {
+            0 => moving,
+            _ => moving.min(speed.div_ceil(rate)),
+        };
+        steps * speed - rate * steps * steps.saturating_sub(1) / 2
+    }
+}
+
+// Speed grows by `rate` mm/ms for each ms held within each `(ms, rate)` step,
+// then by `tail` mm/ms for the rest of the hold. Rates may only go down, which
+// keeps the distance single-peaked.
+struct Piecewise {
+    steps: Vec<(u64, u64)>,
+    tail: u64,
+}
+
+impl Piecewise {
+    fn new(steps: Vec<(u64, u64)>, tail: u64) -> Result<Piecewise> {
+        let rates = steps
+            .iter()
+            .map(|(_, rate)| *rate)
+            .chain([tail])
+            .collect::<Vec<u64>>();
+        if rates.windows(2).any(|w| w[1] > w[0]) {
+            return Err(anyhow!("piecewise rates must not increase: {:?}", rates));
+        }
+        Ok(Piecewise { steps, tail })
+    }
+
+    fn speed(&self, hold: u64) -> u128 {
+        let mut left = hold;
+        let mut speed = 0u128;
+        for (ms, rate) in &self.steps {
+            let held = left.min(*ms);
+            speed += held as u128 * *rate as u128;
+            left -= held;
+        }
+        speed + left as u128 * self.tail as u128
+    }
+}
+
+impl Model for Piecewise {
+    fn distance(&self, hold: u64, time: u64) -> u128 {
+        self.speed(hold)
+            .saturating_mul(time.saturating_sub(hold) as u128)
+    }
+}
+
+// The fallback for models without a closed form: binary searches for the
+// peak, then for the first and last holds beating the record on either side.
+fn search_holds<M: Model + ?Sized>(model: &M, race: &Race) -> Option<(u64, u64)> {
+    let distance = |hold| model.distance(hold, race.time);
+    let record = race.distance as u128;
+    let peak = model.peak(race.time);
+    if distance(peak) <= record {
+        return None;
+    }
+    let min = first_hold(0, peak, |h| distance(h) > record);
+    let max = first_hold(peak, race.time, |h| distance(h + 1) <= record);
+    Some((min, max))
+}
+
+// The first hold in `lo..hi` for which `pred` holds, or `hi` if there's none.
+// `pred` must be false and then true across the range.
+fn first_hold<F: Fn(u64) -> bool>(mut lo: u64, mut hi: u64, pred: F) -> u64 {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    lo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_model, read_input, search_holds, sols, Capped, Decay, Linear, Model, Piecewise, Race,
+        Report, Sheet,
+    };
+    use anyhow::Result;
+    use num_bigint::BigUint;
+
+    fn brute_force(time: u64, distance: u64) -> u64 {
+        (0..=time).filter(|h| h * (time - h) > distance).count() as u64
+    }
+
+    #[test]
+    fn test_sols() {
+        assert_eq!(sols(&7u64, &9u64), 4);
+        assert_eq!(sols(&15u64, &40u64), 8);
+        assert_eq!(sols(&30u64, &200u64), 9);
+        assert_eq!(sols(&71530u64, &940200u64), 71503);
+        assert_eq!(sols(&0u64, &0u64), 0);
+        assert_eq!(sols(&3u64, &2u64), 0);
+        assert_eq!(sols(&10u64, &100u64), 0);
+        assert_eq!(sols(&u64::MAX, &u64::MAX), u64::MAX - 3);
+    }
+
+    #[test]
+    fn test_sols_wide() {
+        // The square of the time overflows `u128`, so this takes the binary
+        // search path, while `BigUint` gets there from the square root.
+        let time = u128::MAX - 12345;
+        let distance = u128::MAX / 5;
+        let expected = sols(&BigUint::from(time), &BigUint::from(distance));
+        assert_eq!(BigUint::from(sols(&time, &distance)), expected);
+
+        // 10^40 and 10^79 + 7: only a big integer can hold these.
+        let time = BigUint::from(10u32).pow(40);
+        let distance = BigUint::from(10u32).pow(79) + 7u32;
+        let ways = sols(&time, &distance);
+        let min = (time.clone() - ways.clone() + 1u32) / 2u32;
+        assert!(min.clone() * (time.clone() - min.clone()) > distance);
+        let before = min.clone() - 1u32;
+        assert!(before.clone() * (time - before) <= distance);
+    }
+
+    // Scans every hold, and checks the winners form a single interval.
+    fn scan_holds(model: &dyn Model, race: &Race) -> Option<(u64, u64)> {
+        let wins = (0..=race.time)
+            .filter(|h| model.distance(*h, race.time) > race.distance as u128)
+            .collect::<Vec<u64>>();
+        let (min, max) = (*wins.first()?, *wins.last()?);
+        assert_eq!(wins.len() as u64, max - min + 1);
+        Some((min, max))
+    }
+
+    #[test]
+    fn test_models() -> Result<()> {
+        let models: Vec<(&str, Box<dyn Model>)> = vec![
+            ("linear", Box::new(Linear)),
+            ("capped:0", Box::new(Capped(0))),
+            ("capped:5", Box::new(Capped(5))),
+            ("capped:1000", Box::new(Capped(1000))),
+            ("decay:1", Box::new(Decay(1))),
+            ("decay:3", Box::new(Decay(3))),
+            ("decay:0", Box::new(Decay(0))),
+            (
+                "piecewise:3x4,1",
+                Box::new(Piecewise::new(vec![(4, 3)], 1)?),
+            ),
+            (
+                "piecewise:5x2,2x3,0",
+                Box::new(Piecewise::new(vec![(2, 5), (3, 2)], 0)?),
+            ),
+        ];
+        for time in 0..60 {
+            for distance in (0..400).step_by(7) {
+                let race = Race { time, distance };
+                for (name, model) in &models {
+                    let expected = scan_holds(model.as_ref(), &race);
+                    assert_eq!(model.holds(&race), expected, "{} {:?}", name, race);
+                    assert_eq!(
+                        search_holds(model.as_ref(), &race),
+                        expected,
+                        "{} {:?}",
+                        name,
+                        race
+                    );
+                }
+                assert_eq!(Linear.ways(&race), sols(&time, &distance));
+                for (name, model) in &models {
+                    let peak = model.peak(time);
+                    let best = (0..=time).map(|h| model.distance(h, time)).max().unwrap();
+                    assert_eq!(model.distance(peak, time), best, "{} {:?}", name, race);
+                }
+                assert_eq!(Decay(0).holds(&race), Linear.holds(&race));
+                assert_eq!(Capped(1000).holds(&race), Linear.holds(&race));
+            }
+        }
+        assert_eq!(Decay(1).distance(3, 10), 3 + 2 + 1);
+        assert_eq!(Decay(2).distance(5, 8), 5 + 3 + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() {
+        let report = Report::new(
+            &Linear,
+            &Race {
+                time: 30,
+                distance: 200,
+            },
+        );
+        assert_eq!(
+            report,
+            Report {
+                time: 30,
+                record: 200,
+                holds: Some((11, 19)),
+                ways: 9,
+                best_hold: 15,
+                best_distance: 225,
+                margin: 25,
+            }
+        );
+        let report = Report::new(
+            &Capped(2),
+            &Race {
+                time: 7,
+                distance: 9,
+            },
+        );
+        assert_eq!((report.holds, report.best_hold), (Some((2, 2)), 2));
+        assert_eq!((report.best_distance, report.margin), (10, 1));
+        let report = Report::new(
+            &Capped(1),
+            &Race {
+                time: 7,
+                distance: 9,
+            },
+        );
+        assert_eq!(
+            (report.ways, report.best_distance, report.margin),
+            (0, 6, -3)
+        );
+    }
+
+    #[test]
+    fn test_parse_model() {
+        let race = Race {
+            time: 30,
+            distance: 200,
+        };
+        assert_eq!(parse_model("linear").unwrap().ways(&race), 9);
+        assert_eq!(parse_model("capped:12").unwrap().ways(&race), 3);
+        assert_eq!(parse_model("piecewise:1x10,1").unwrap().ways(&race), 9);
+        assert!(parse_model("piecewise:1x10,2").is_err());
+        assert!(parse_model("piecewise:1-10,1").is_err());
+        assert!(parse_model("capped").is_err());
+        assert!(parse_model("quadratic").is_err());
+    }
+
+    #[test]
+    fn test_parse_sheet() -> Result<()> {
+        let sheet = Sheet::parse(&read_input("input-small.txt")?)?;
+        assert_eq!(
+            sheet.races,
+            vec![
+                Race {
+                    time: 7,
+                    distance: 9
+                },
+                Race {
+                    time: 15,
+                    distance: 40
+                },
+                Race {
+                    time: 30,
+                    distance: 200
+                },
+            ]
+        );
+        assert_eq!(
+            sheet.merged.narrow::<u64>()?,
+            Race {
+                time: 71530,
+                distance: 940200
+            }
+        );
+
+        let lines = |time: &str, distance: &str| vec![time.to_string(), distance.to_string()];
+        assert!(Sheet::parse(&lines("Time: 7 15", "Distance: 9")).is_err());
+        assert!(Sheet::parse(&lines("Distance: 9", "Time: 7")).is_err());
+        assert!(Sheet::parse(&lines("Time: 7 x", "Distance: 9 40")).is_err());
+        assert!(Sheet::parse(&lines("Time:", "Distance:")).is_err());
+        // Each race fits, but the merged distance has 21 digits.
+        let sheet = Sheet::parse(&lines("Time: 1 2", "Distance: 9999999999 99999999999"))?;
+        let err = sheet.merged.narrow::<u64>().unwrap_err();
+        assert!(err.to_string().contains("does not fit in u64"));
+        assert!(sheet.merged.narrow::<u128>().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sols_brute_force() {
+        let mut x = 42u64;
+        let mut next = || {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            x >> 33
+        };
+        for _ in 0..2000 {
+            let time = next() % 300;
+            let best = (time / 2) * (time - time / 2);
+            let distance = next() % (best + 10);
+            assert_eq!(sols(&time, &distance), brute_force(time, distance));
+            // Records hit exactly by some hold make the discriminant a
+            // perfect square; the hold itself must not count as a win.
+            let hold = next() % (time + 1);
+            let distance = hold * (time - hold);
+            assert_eq!(sols(&time, &distance), brute_force(time, distance));
+            if distance > 0 {
+                assert_eq!(
+                    sols(&time, &(distance - 1)),
+                    brute_force(time, distance - 1)
+                );
+            }
+            let wide = sols(&BigUint::from(time), &BigUint::from(distance));
+            assert_eq!(wide, BigUint::from(brute_force(time, distance)));
+        }
+    }
 }